@@ -0,0 +1,293 @@
+use serde::Serialize;
+
+use crate::interface::chessboard::piece::{ChessPiece, Color};
+
+use super::square::{Square, ALL_WAYS, KNIGHT_JUMPS};
+
+// a piece that can't move off the line between its king and an enemy slider
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pin {
+    pub pinned: i32,
+    pub pinner: i32,
+    // squares from next to the king up to the pinner, the pinned piece can only move along them
+    pub ray: Vec<i32>,
+}
+
+// color of the piece without going through its fen letter, None for an empty square
+pub fn piece_color(piece: ChessPiece) -> Option<Color> {
+    match piece {
+        ChessPiece::None => None,
+        ChessPiece::WPawn
+        | ChessPiece::WKnight
+        | ChessPiece::WBishop
+        | ChessPiece::WRook
+        | ChessPiece::WQueen
+        | ChessPiece::WKing => Some(Color::White),
+        _ => Some(Color::Black),
+    }
+}
+
+// the board index `files` and `ranks` away, see Square::offset
+fn step(square: i32, (files, ranks): (i32, i32)) -> Option<i32> {
+    Square::new(square)?.offset(files, ranks).map(Square::index)
+}
+
+// true for the pieces that slide along `direction`
+fn slides(piece: ChessPiece, direction: (i32, i32)) -> bool {
+    let diagonal = direction.0 != 0 && direction.1 != 0;
+    match piece {
+        ChessPiece::WQueen | ChessPiece::BQueen => true,
+        ChessPiece::WBishop | ChessPiece::BBishop => diagonal,
+        ChessPiece::WRook | ChessPiece::BRook => !diagonal,
+        _ => false,
+    }
+}
+
+// the first two pieces met going from `square` along `direction`, with every square passed
+// on the way up to the second one
+fn first_two(
+    board: &[ChessPiece; 64],
+    square: i32,
+    direction: (i32, i32),
+) -> Option<(i32, i32, Vec<i32>)> {
+    let mut first = None;
+    let mut ray = vec![];
+    let mut current = square;
+    while let Some(next) = step(current, direction) {
+        ray.push(next);
+        if board[next as usize] != ChessPiece::None {
+            match first {
+                None => first = Some(next),
+                Some(first) => return Some((first, next, ray)),
+            }
+        }
+        current = next;
+    }
+    None
+}
+
+pub fn king_square(board: &[ChessPiece; 64], color: Color) -> Option<i32> {
+    let king = if color == Color::White {
+        ChessPiece::WKing
+    } else {
+        ChessPiece::BKing
+    };
+    board
+        .iter()
+        .position(|piece| *piece == king)
+        .map(|position| position as i32)
+}
+
+// every square the pieces of `color` attack, one bit per square, whatever stands on it
+pub fn attack_map(board: &[ChessPiece; 64], color: Color) -> u64 {
+    let forward = if color == Color::White { 1 } else { -1 };
+    let mut attacks = 0;
+    for (position, piece) in board.iter().enumerate() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        let position = position as i32;
+        let mut hit = |square: Option<i32>| {
+            if let Some(square) = square {
+                attacks |= 1 << square;
+            }
+        };
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => {
+                hit(step(position, (-1, forward)));
+                hit(step(position, (1, forward)));
+            }
+            ChessPiece::WKnight | ChessPiece::BKnight => {
+                KNIGHT_JUMPS
+                    .iter()
+                    .for_each(|jump| hit(step(position, *jump)));
+            }
+            ChessPiece::WKing | ChessPiece::BKing => {
+                ALL_WAYS
+                    .iter()
+                    .for_each(|direction| hit(step(position, *direction)));
+            }
+            _ => {
+                for direction in ALL_WAYS.iter() {
+                    if !slides(*piece, *direction) {
+                        continue;
+                    }
+                    let mut current = position;
+                    while let Some(next) = step(current, *direction) {
+                        hit(Some(next));
+                        if board[next as usize] != ChessPiece::None {
+                            break;
+                        }
+                        current = next;
+                    }
+                }
+            }
+        }
+    }
+    attacks
+}
+
+// the squares of attack_map as a list, for the board overlays
+pub fn attacked_squares(board: &[ChessPiece; 64], color: Color) -> Vec<i32> {
+    let attacks = attack_map(board, color);
+    (0..64)
+        .filter(|square| attacks & (1 << square) != 0)
+        .collect()
+}
+
+// calls `found` with every piece of `color` attacking `square` until it returns true
+pub fn visit_attackers(
+    board: &[ChessPiece; 64],
+    square: i32,
+    color: Color,
+    mut found: impl FnMut(i32) -> bool,
+) -> bool {
+    let (pawn, knight, king) = if color == Color::White {
+        (ChessPiece::WPawn, ChessPiece::WKnight, ChessPiece::WKing)
+    } else {
+        (ChessPiece::BPawn, ChessPiece::BKnight, ChessPiece::BKing)
+    };
+    // pawns attack forward, so they stand one rank behind the square
+    let behind = if color == Color::White { -1 } else { 1 };
+
+    for direction in ALL_WAYS.iter() {
+        let mut current = square;
+        while let Some(next) = step(current, *direction) {
+            let piece = board[next as usize];
+            if piece == ChessPiece::None {
+                current = next;
+                continue;
+            }
+            let adjacent = current == square;
+            let pawn_takes = piece == pawn && direction.1 == behind && direction.0 != 0;
+            if piece_color(piece) == Some(color)
+                && (slides(piece, *direction) || adjacent && (piece == king || pawn_takes))
+                && found(next)
+            {
+                return true;
+            }
+            break;
+        }
+    }
+    for jump in KNIGHT_JUMPS {
+        if let Some(from) = step(square, jump) {
+            if board[from as usize] == knight && found(from) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// pieces of `color` that attack `square` with the board as it is, so a piece behind another
+// one on the same line only shows up once the front one is gone; pieces defending one of
+// their own on the square count as well
+pub fn attackers_of(board: &[ChessPiece; 64], square: i32, color: Color) -> Vec<i32> {
+    let mut attackers = vec![];
+    visit_attackers(board, square, color, |from| {
+        attackers.push(from);
+        false
+    });
+    attackers
+}
+
+// whether any piece of `color` attacks `square`, without listing them
+pub fn is_attacked(board: &[ChessPiece; 64], square: i32, color: Color) -> bool {
+    visit_attackers(board, square, color, |_| true)
+}
+
+// pieces of the opponent giving check to the king of `color`
+pub fn checkers(board: &[ChessPiece; 64], color: Color) -> Vec<i32> {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    king_square(board, color)
+        .map(|king| attackers_of(board, king, opponent))
+        .unwrap_or_default()
+}
+
+// pieces of `color` that can't leave the line to their own king without exposing it
+pub fn pins(board: &[ChessPiece; 64], color: Color) -> Vec<Pin> {
+    let king = match king_square(board, color) {
+        Some(king) => king,
+        None => return vec![],
+    };
+    ALL_WAYS
+        .iter()
+        .filter_map(|direction| {
+            let (first, second, ray) = first_two(board, king, *direction)?;
+            let pinner = board[second as usize];
+            (piece_color(board[first as usize]) == Some(color)
+                && piece_color(pinner) != Some(color)
+                && slides(pinner, *direction))
+            .then_some(Pin {
+                pinned: first,
+                pinner: second,
+                ray,
+            })
+        })
+        .collect()
+}
+
+// pieces of `color` standing between one of their own sliders and the opponent's king,
+// moving them off the line gives a discovered check
+pub fn discovered_check_candidates(board: &[ChessPiece; 64], color: Color) -> Vec<i32> {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let king = match king_square(board, opponent) {
+        Some(king) => king,
+        None => return vec![],
+    };
+    ALL_WAYS
+        .iter()
+        .filter_map(|direction| {
+            let (first, second, _) = first_two(board, king, *direction)?;
+            let slider = board[second as usize];
+            (piece_color(board[first as usize]) == Some(color)
+                && piece_color(slider) == Some(color)
+                && slides(slider, *direction))
+            .then_some(first)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::chessboard::piece::fen_to_board;
+
+    #[test]
+    fn test_attack_queries() {
+        // the rook on e1 pins the knight on e5, the bishop on b5 checks the king on e8
+        let board = fen_to_board("4k3/8/8/1B2n3/8/8/8/4RK2 b - - 0 1");
+        assert_eq!(checkers(&board, Color::Black), vec![25]);
+        assert_eq!(
+            pins(&board, Color::Black),
+            vec![Pin {
+                pinned: 28,
+                pinner: 60,
+                ray: vec![12, 20, 28, 36, 44, 52, 60],
+            }]
+        );
+        assert!(discovered_check_candidates(&board, Color::White).is_empty());
+        let attacks = attack_map(&board, Color::White);
+        assert!(attacks & (1 << 4) != 0 && attacks & (1 << 28) != 0);
+        assert!(attacks & (1 << 12) == 0);
+
+        // the white knight on d4 steps off the bishop's diagonal with check
+        let board = fen_to_board("7k/8/8/8/3N4/8/1B6/4K3 w - - 0 1");
+        assert_eq!(discovered_check_candidates(&board, Color::White), vec![35]);
+        assert!(checkers(&board, Color::Black).is_empty());
+
+        // both side pawns and the knight hit d5, the rook is stuck behind its own pawn
+        let board = fen_to_board("4k3/8/8/3p4/2PPP3/2N5/3R4/4K3 w - - 0 1");
+        let mut attackers = attackers_of(&board, 27, Color::White);
+        attackers.sort();
+        assert_eq!(attackers, vec![34, 36, 42]);
+    }
+}
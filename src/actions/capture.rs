@@ -1,69 +1,22 @@
-use crate::{
-    actions::path::{pawn_capture_squares, rook_possible_squares},
-    interface::chessboard::piece::{ChessPiece, Color},
-};
+use crate::interface::chessboard::piece::{ChessPiece, Color};
 
-use super::path::{bishop_possible_squares, knight_possible_squares, queen_attacking_squares};
+use super::{
+    attacks::{attackers_of, is_attacked, king_square, piece_color, visit_attackers},
+    evaluation::{material_value, MaterialParams},
+    packed::PackedMove,
+    params::eval_params,
+    play::MoveType,
+    square::Square,
+};
 
+// whether the king of `color` is attacked right now
 pub fn in_check(board: &[ChessPiece; 64], color: Color) -> bool {
-    let king = if color == Color::White {
-        ChessPiece::WKing
-    } else {
-        ChessPiece::BKing
-    };
-
-    let mut king_pos = 0;
-    for (i, piece) in board.iter().enumerate() {
-        if *piece == king {
-            king_pos = i;
-            break;
-        }
-    }
-    let king_pos = king_pos as i32;
-    // Check if the king is attacked by any opponent's piece
-    let opponent_color = if color == Color::White {
+    let opponent = if color == Color::White {
         Color::Black
     } else {
         Color::White
     };
-
-    // Check if any opponent's pawn, bishop, rook, queen, or knight can attack the king's position
-    let opponent_pieces = get_pieces_by_color(board, opponent_color);
-    let mut attacking_squares: Vec<i32> = vec![];
-    for &(piece, piece_pos) in &opponent_pieces {
-        match piece {
-            ChessPiece::BPawn | ChessPiece::WPawn => {
-                attacking_squares.extend(pawn_capture_squares(board, piece, piece_pos));
-            }
-            ChessPiece::BBishop | ChessPiece::WBishop => {
-                let ba: (Vec<i32>, Vec<i32>) = bishop_possible_squares(board, piece, piece_pos);
-                attacking_squares.extend(ba.0);
-                attacking_squares.extend(ba.1);
-            }
-            ChessPiece::BRook | ChessPiece::WRook => {
-                let rook_possible_squares = rook_possible_squares(board, piece, piece_pos);
-                attacking_squares.extend(rook_possible_squares.0);
-                attacking_squares.extend(rook_possible_squares.1);
-            }
-            ChessPiece::BQueen | ChessPiece::WQueen => {
-                let qa: (Vec<i32>, Vec<i32>) = queen_attacking_squares(board, piece, piece_pos);
-                attacking_squares.extend(qa.0);
-                attacking_squares.extend(qa.1);
-            }
-            ChessPiece::BKnight | ChessPiece::WKnight => {
-                let knight_possible_squares = knight_possible_squares(board, piece, piece_pos);
-                attacking_squares.extend(knight_possible_squares.0);
-                attacking_squares.extend(knight_possible_squares.1);
-            }
-            _ => continue, // Skip other pieces
-        }
-    }
-    attacking_squares.sort();
-    attacking_squares.dedup();
-    if attacking_squares.contains(&king_pos) {
-        return true;
-    }
-    false
+    king_square(board, color).is_some_and(|king| is_attacked(board, king, opponent))
 }
 
 // Helper function to get all pieces of a specific color
@@ -77,3 +30,149 @@ pub fn get_pieces_by_color(board: &[ChessPiece; 64], color: Color) -> Vec<(Chess
     }
     pieces
 }
+
+fn least_valuable_attacker(
+    board: &[ChessPiece; 64],
+    square: i32,
+    color: Color,
+    values: &MaterialParams,
+) -> Option<i32> {
+    let mut least: Option<(i32, i32)> = None;
+    visit_attackers(board, square, color, |position| {
+        let value = match board[position as usize] {
+            // the king goes last, it can only take when nothing is left to take it back
+            ChessPiece::WKing | ChessPiece::BKing => i32::MAX,
+            piece => material_value(values, piece),
+        };
+        if least.is_none_or(|(_, least_value)| value < least_value) {
+            least = Some((position, value));
+        }
+        false
+    });
+    least.map(|(position, _)| position)
+}
+
+// static exchange evaluation: material won or lost by `mv` once both sides have
+// recaptured on its square with their least valuable attacker for as long as it pays
+pub fn see(board: &[ChessPiece; 64], mv: PackedMove, values: &MaterialParams) -> i32 {
+    let mut board = *board;
+    let (from, to) = (mv.from(), mv.to());
+    let square = to.index();
+    // what the side to move is up after each capture, before the other side answers; every
+    // capture takes a piece off the board so there are never more than 32
+    let mut gains = [0; 32];
+    let mut depth = 0;
+
+    let mut captured = board[square as usize];
+    if mv.move_type() == MoveType::EnPassant {
+        let beside = Square::at(to.file(), from.rank()).index();
+        captured = board[beside as usize];
+        board[beside as usize] = ChessPiece::None;
+    }
+    gains[0] = material_value(values, captured);
+    let mut piece = board[from.index() as usize];
+    let color = piece_color(piece).unwrap_or(Color::White);
+    if let Some(promotion) = mv.promotion() {
+        let promoted = promotion.piece(color);
+        gains[0] += material_value(values, promoted) - material_value(values, piece);
+        piece = promoted;
+    }
+    board[from.index() as usize] = ChessPiece::None;
+    board[square as usize] = piece;
+
+    let mut color = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    while let Some(attacker) = least_valuable_attacker(&board, square, color, values) {
+        let opponent = if color == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let attacker_piece = board[attacker as usize];
+        let is_king = attacker_piece == ChessPiece::WKing || attacker_piece == ChessPiece::BKing;
+        if (is_king && is_attacked(&board, square, opponent)) || depth + 1 == gains.len() {
+            break;
+        }
+        depth += 1;
+        gains[depth] = material_value(values, board[square as usize]) - gains[depth - 1];
+        board[attacker as usize] = ChessPiece::None;
+        board[square as usize] = attacker_piece;
+        color = opponent;
+    }
+
+    // either side can stop taking when going on would cost it
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+// pieces of `color` the opponent wins material by taking, for warnings on the board
+pub fn hanging_pieces(board: &[ChessPiece; 64], color: Color) -> Vec<i32> {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let values = &eval_params().material;
+    get_pieces_by_color(board, color)
+        .into_iter()
+        .filter(|(piece, position)| {
+            *piece != ChessPiece::WKing
+                && *piece != ChessPiece::BKing
+                && attackers_of(board, *position, opponent)
+                    .into_iter()
+                    .any(|from| {
+                        let capture = Square::new(from)
+                            .zip(Square::new(*position))
+                            .map(|(from, to)| PackedMove::new(from, to, MoveType::Normal, None));
+                        capture.is_some_and(|capture| see(board, capture, values) > 0)
+                    })
+        })
+        .map(|(_, position)| position)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::chessboard::piece::fen_to_board;
+
+    fn capture(from: i32, to: i32) -> PackedMove {
+        PackedMove::new(
+            Square::new(from).unwrap(),
+            Square::new(to).unwrap(),
+            MoveType::Normal,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_see() {
+        let values = &eval_params().material;
+        let (pawn, knight, rook) = (values.pawn, values.knight, values.rook);
+
+        // rook takes an undefended pawn
+        let board = fen_to_board("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1");
+        assert_eq!(see(&board, capture(60, 28), values), pawn);
+
+        // knight takes a pawn defended by a pawn, the queen behind the bishop joins in
+        // through the x-ray but it's still a knight for a pawn
+        let board = fen_to_board("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1");
+        assert_eq!(see(&board, capture(43, 28), values), pawn - knight);
+
+        // the rook behind the rook makes the second capture safe
+        let board = fen_to_board("3r3k/8/8/3p4/8/8/3R4/3R3K w - - 0 1");
+        assert_eq!(see(&board, capture(51, 27), values), pawn);
+        let board = fen_to_board("3r3k/3r4/8/3p4/8/8/3R4/7K w - - 0 1");
+        assert_eq!(see(&board, capture(51, 27), values), pawn - rook);
+
+        // the knight on e5 can be taken for nothing
+        let board = fen_to_board("4k3/8/8/4n3/8/5N2/8/4K3 b - - 0 1");
+        assert_eq!(hanging_pieces(&board, Color::Black), vec![28]);
+    }
+}
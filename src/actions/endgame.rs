@@ -0,0 +1,917 @@
+use std::{collections::HashMap, fs};
+
+use crate::interface::chessboard::piece::{fen_to_board, ChessPiece, Color};
+
+use super::{
+    capture::in_check,
+    path::PossibleMoves,
+    play::Game,
+    player::to_move,
+    square::{Square, ALL_WAYS, DIAGONAL, KNIGHT_JUMPS, STRAIGHT},
+};
+
+pub const ENDGAME_TABLE_EXTENSION: &str = "cmtb";
+// kings included, tables grow 64 times with every piece added
+pub const MAX_ENDGAME_PIECES: usize = 4;
+
+const MAGIC: &[u8; 4] = b"CMTB";
+const FORMAT_VERSION: u8 = 1;
+
+// distance to mate in moves for the side to move, Loss(0) is already checkmated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u8),
+    Draw,
+    Loss(u8),
+}
+
+impl Dtm {
+    // one byte per position in the table files: 0 draw, 1..=127 win, 128.. loss
+    fn from_byte(byte: u8) -> Dtm {
+        match byte {
+            0 => Dtm::Draw,
+            1..=127 => Dtm::Win(byte),
+            _ => Dtm::Loss(byte - 128),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Dtm::Draw => 0,
+            Dtm::Win(moves) => moves.min(127),
+            Dtm::Loss(moves) => 128 + moves.min(127),
+        }
+    }
+
+    // result for the side that just moved into a position with this result
+    pub fn parent(self) -> Dtm {
+        match self {
+            Dtm::Win(moves) => Dtm::Loss(moves),
+            Dtm::Draw => Dtm::Draw,
+            Dtm::Loss(moves) => Dtm::Win(moves + 1),
+        }
+    }
+
+    // half moves until mate, 0 for a draw
+    pub fn plies(self) -> u32 {
+        match self {
+            Dtm::Win(moves) => 2 * moves as u32 - 1,
+            Dtm::Draw => 0,
+            Dtm::Loss(moves) => 2 * moves as u32,
+        }
+    }
+
+    // higher is better for the side to move: quick wins, then draws, then slow losses
+    fn preference(self) -> i32 {
+        match self {
+            Dtm::Win(moves) => 1000 - moves as i32,
+            Dtm::Draw => 0,
+            Dtm::Loss(moves) => -1000 + moves as i32,
+        }
+    }
+}
+
+// every position of one material balance, indexed by side to move and piece squares
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndgameTable {
+    pub name: String,
+    pieces: Vec<ChessPiece>,
+    values: Vec<u8>,
+}
+
+// pieces of a table name such as KQvK or KRPvKR, white's side first
+pub fn parse_material(name: &str) -> Option<Vec<ChessPiece>> {
+    let (white, black) = name.split_once('v')?;
+    let mut pieces = vec![];
+    for (side, color) in [(white, Color::White), (black, Color::Black)] {
+        if !side.starts_with('K') || side.matches('K').count() != 1 {
+            return None;
+        }
+        for letter in side.chars() {
+            pieces.push(piece_for(letter, color)?);
+        }
+    }
+    if pieces.len() > MAX_ENDGAME_PIECES {
+        return None;
+    }
+    Some(pieces)
+}
+
+fn piece_for(letter: char, color: Color) -> Option<ChessPiece> {
+    let white = color == Color::White;
+    let piece = match letter {
+        'K' if white => ChessPiece::WKing,
+        'K' => ChessPiece::BKing,
+        'Q' if white => ChessPiece::WQueen,
+        'Q' => ChessPiece::BQueen,
+        'R' if white => ChessPiece::WRook,
+        'R' => ChessPiece::BRook,
+        'B' if white => ChessPiece::WBishop,
+        'B' => ChessPiece::BBishop,
+        'N' if white => ChessPiece::WKnight,
+        'N' => ChessPiece::BKnight,
+        'P' if white => ChessPiece::WPawn,
+        'P' => ChessPiece::BPawn,
+        _ => return None,
+    };
+    Some(piece)
+}
+
+fn is_white(piece: ChessPiece) -> bool {
+    matches!(
+        piece,
+        ChessPiece::WKing
+            | ChessPiece::WQueen
+            | ChessPiece::WRook
+            | ChessPiece::WBishop
+            | ChessPiece::WKnight
+            | ChessPiece::WPawn
+    )
+}
+
+fn swap_color(piece: ChessPiece) -> ChessPiece {
+    match piece {
+        ChessPiece::WKing => ChessPiece::BKing,
+        ChessPiece::WQueen => ChessPiece::BQueen,
+        ChessPiece::WRook => ChessPiece::BRook,
+        ChessPiece::WBishop => ChessPiece::BBishop,
+        ChessPiece::WKnight => ChessPiece::BKnight,
+        ChessPiece::WPawn => ChessPiece::BPawn,
+        ChessPiece::BKing => ChessPiece::WKing,
+        ChessPiece::BQueen => ChessPiece::WQueen,
+        ChessPiece::BRook => ChessPiece::WRook,
+        ChessPiece::BBishop => ChessPiece::WBishop,
+        ChessPiece::BKnight => ChessPiece::WKnight,
+        ChessPiece::BPawn => ChessPiece::WPawn,
+        ChessPiece::None => ChessPiece::None,
+    }
+}
+
+pub fn piece_count(board: &[ChessPiece; 64]) -> usize {
+    board
+        .iter()
+        .filter(|piece| **piece != ChessPiece::None)
+        .count()
+}
+
+fn side_key(board: &[ChessPiece; 64], color: Color) -> String {
+    let order = if color == Color::White {
+        [
+            ChessPiece::WKing,
+            ChessPiece::WQueen,
+            ChessPiece::WRook,
+            ChessPiece::WBishop,
+            ChessPiece::WKnight,
+            ChessPiece::WPawn,
+        ]
+    } else {
+        [
+            ChessPiece::BKing,
+            ChessPiece::BQueen,
+            ChessPiece::BRook,
+            ChessPiece::BBishop,
+            ChessPiece::BKnight,
+            ChessPiece::BPawn,
+        ]
+    };
+    let mut key = String::new();
+    for (piece, letter) in order.iter().zip(['K', 'Q', 'R', 'B', 'N', 'P']) {
+        let count = board.iter().filter(|p| *p == piece).count();
+        key.extend(std::iter::repeat_n(letter, count));
+    }
+    key
+}
+
+// table names for the material on the board, white's pieces first and black's first
+pub fn material_keys(board: &[ChessPiece; 64]) -> (String, String) {
+    let white = side_key(board, Color::White);
+    let black = side_key(board, Color::Black);
+    (
+        format!("{}v{}", white, black),
+        format!("{}v{}", black, white),
+    )
+}
+
+// the board seen from the other side, ranks mirrored and colors swapped
+fn flip_board(board: &[ChessPiece; 64]) -> [ChessPiece; 64] {
+    let mut flipped = [ChessPiece::None; 64];
+    for (position, piece) in board.iter().enumerate() {
+        let row = position / 8;
+        let col = position % 8;
+        flipped[(7 - row) * 8 + col] = swap_color(*piece);
+    }
+    flipped
+}
+
+// side to move in the top bit, then six bits of square for every piece
+fn position_index(squares: &[i32], white_to_move: bool) -> usize {
+    let mut index = if white_to_move { 0 } else { 1 };
+    for square in squares {
+        index = (index << 6) | *square as usize;
+    }
+    index
+}
+
+fn decode_index(index: usize, count: usize) -> ([i32; MAX_ENDGAME_PIECES], bool) {
+    let mut squares = [0; MAX_ENDGAME_PIECES];
+    for (i, square) in squares.iter_mut().take(count).enumerate() {
+        *square = ((index >> (6 * (count - 1 - i))) & 63) as i32;
+    }
+    let white_to_move = (index >> (6 * count)) & 1 == 0;
+    (squares, white_to_move)
+}
+
+impl EndgameTable {
+    pub fn pieces(&self) -> &[ChessPiece] {
+        &self.pieces
+    }
+
+    // squares of the table's pieces on this board, None when the material is different
+    fn squares_on(&self, board: &[ChessPiece; 64]) -> Option<[i32; MAX_ENDGAME_PIECES]> {
+        if piece_count(board) != self.pieces.len() {
+            return None;
+        }
+        let mut squares = [0; MAX_ENDGAME_PIECES];
+        let mut used = [false; 64];
+        for (slot, piece) in self.pieces.iter().enumerate() {
+            let square = (0..64).find(|&square| !used[square] && board[square] == *piece)?;
+            used[square] = true;
+            squares[slot] = square as i32;
+        }
+        Some(squares)
+    }
+
+    pub fn probe(&self, board: &[ChessPiece; 64], white_to_move: bool) -> Option<Dtm> {
+        let squares = self.squares_on(board)?;
+        let index = position_index(&squares[..self.pieces.len()], white_to_move);
+        self.values.get(index).map(|byte| Dtm::from_byte(*byte))
+    }
+
+    // header, table name, then the values run length encoded as (value, run) byte pairs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        bytes.push(self.name.len() as u8);
+        bytes.extend_from_slice(self.name.as_bytes());
+        let mut i = 0;
+        while i < self.values.len() {
+            let value = self.values[i];
+            let mut run = 1;
+            while run < 255 && i + run < self.values.len() && self.values[i + run] == value {
+                run += 1;
+            }
+            bytes.push(value);
+            bytes.push(run as u8);
+            i += run;
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 6 || &bytes[..4] != MAGIC || bytes[4] != FORMAT_VERSION {
+            return Err(String::from("not an endgame table"));
+        }
+        let name_end = 6 + bytes[5] as usize;
+        let name = bytes
+            .get(6..name_end)
+            .and_then(|name| String::from_utf8(name.to_vec()).ok())
+            .ok_or("bad table name")?;
+        let pieces = parse_material(&name).ok_or(format!("bad table name {}", name))?;
+        let size = 2 << (6 * pieces.len());
+        let mut values = Vec::with_capacity(size);
+        for pair in bytes[name_end..].chunks_exact(2) {
+            values.extend(std::iter::repeat_n(pair[0], pair[1] as usize));
+        }
+        if values.len() != size {
+            return Err(format!("{} is truncated", name));
+        }
+        Ok(EndgameTable {
+            name,
+            pieces,
+            values,
+        })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+        Self::from_bytes(&bytes).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_bytes()).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    // longest win in the table, in moves
+    pub fn longest_mate(&self) -> u8 {
+        self.values
+            .iter()
+            .filter(|byte| (1..=127).contains(*byte))
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+const PROMOTIONS: [ChessPiece; 4] = [
+    ChessPiece::WQueen,
+    ChessPiece::WRook,
+    ChessPiece::WBishop,
+    ChessPiece::WKnight,
+];
+
+// a position of the table being generated, captured pieces sit on square -1
+#[derive(Clone, Copy)]
+struct Placement<'a> {
+    pieces: &'a [ChessPiece],
+    white: &'a [bool],
+    squares: [i32; MAX_ENDGAME_PIECES],
+}
+
+fn offset(square: i32, (files, ranks): (i32, i32)) -> Option<i32> {
+    Square::new(square)?.offset(files, ranks).map(Square::index)
+}
+
+impl Placement<'_> {
+    fn occupant(&self, square: i32) -> Option<usize> {
+        (0..self.pieces.len()).find(|&slot| self.squares[slot] == square)
+    }
+
+    fn steps(piece: ChessPiece) -> (&'static [(i32, i32)], bool) {
+        match piece {
+            ChessPiece::WKing | ChessPiece::BKing => (&ALL_WAYS, false),
+            ChessPiece::WKnight | ChessPiece::BKnight => (&KNIGHT_JUMPS, false),
+            ChessPiece::WRook | ChessPiece::BRook => (&STRAIGHT, true),
+            ChessPiece::WBishop | ChessPiece::BBishop => (&DIAGONAL, true),
+            // queens slide every way a king steps
+            _ => (&ALL_WAYS, true),
+        }
+    }
+
+    fn attacks(&self, slot: usize, target: i32) -> bool {
+        let from = self.squares[slot];
+        if from < 0 {
+            return false;
+        }
+        let piece = self.pieces[slot];
+        if piece == ChessPiece::WPawn || piece == ChessPiece::BPawn {
+            let forward = if self.white[slot] { 1 } else { -1 };
+            return [-1, 1]
+                .iter()
+                .any(|&files| offset(from, (files, forward)) == Some(target));
+        }
+        let (steps, sliding) = Self::steps(piece);
+        for &step in steps {
+            let mut square = from;
+            while let Some(next) = offset(square, step) {
+                if next == target {
+                    return true;
+                }
+                if !sliding || self.occupant(next).is_some() {
+                    break;
+                }
+                square = next;
+            }
+        }
+        false
+    }
+
+    fn king_attacked(&self, white: bool) -> bool {
+        let king = (0..self.pieces.len())
+            .find(|&slot| self.white[slot] == white && Self::is_king(self.pieces[slot]));
+        match king {
+            Some(king) => (0..self.pieces.len())
+                .any(|slot| self.white[slot] != white && self.attacks(slot, self.squares[king])),
+            None => false,
+        }
+    }
+
+    fn is_king(piece: ChessPiece) -> bool {
+        piece == ChessPiece::WKing || piece == ChessPiece::BKing
+    }
+
+    fn is_pawn(piece: ChessPiece) -> bool {
+        piece == ChessPiece::WPawn || piece == ChessPiece::BPawn
+    }
+
+    // pseudo legal moves of one side as (slot, to, captured slot, promotion)
+    fn moves(&self, white: bool) -> Vec<(usize, i32, Option<usize>, Option<ChessPiece>)> {
+        let mut moves = vec![];
+        for slot in 0..self.pieces.len() {
+            let from = self.squares[slot];
+            if self.white[slot] != white || from < 0 {
+                continue;
+            }
+            let piece = self.pieces[slot];
+            if Self::is_pawn(piece) {
+                self.pawn_moves(slot, &mut moves);
+                continue;
+            }
+            let (steps, sliding) = Self::steps(piece);
+            for &step in steps {
+                let mut square = from;
+                while let Some(next) = offset(square, step) {
+                    match self.occupant(next) {
+                        None => moves.push((slot, next, None, None)),
+                        Some(other) => {
+                            if self.white[other] != white && !Self::is_king(self.pieces[other]) {
+                                moves.push((slot, next, Some(other), None));
+                            }
+                            break;
+                        }
+                    }
+                    if !sliding {
+                        break;
+                    }
+                    square = next;
+                }
+            }
+        }
+        moves
+    }
+
+    fn pawn_moves(
+        &self,
+        slot: usize,
+        moves: &mut Vec<(usize, i32, Option<usize>, Option<ChessPiece>)>,
+    ) {
+        let from = self.squares[slot];
+        let white = self.white[slot];
+        let forward = if white { 1 } else { -1 };
+        let start_row = if white { 6 } else { 1 };
+        let last_row = if white { 0 } else { 7 };
+        let mut push = |to: i32, captured: Option<usize>| {
+            if to / 8 == last_row {
+                for promotion in PROMOTIONS {
+                    let promotion = if white {
+                        promotion
+                    } else {
+                        swap_color(promotion)
+                    };
+                    moves.push((slot, to, captured, Some(promotion)));
+                }
+            } else {
+                moves.push((slot, to, captured, None));
+            }
+        };
+
+        if let Some(one) = offset(from, (0, forward)) {
+            if self.occupant(one).is_none() {
+                push(one, None);
+                if from / 8 == start_row {
+                    if let Some(two) = offset(one, (0, forward)) {
+                        if self.occupant(two).is_none() {
+                            push(two, None);
+                        }
+                    }
+                }
+            }
+        }
+        for files in [-1, 1] {
+            if let Some(to) = offset(from, (files, forward)) {
+                if let Some(other) = self.occupant(to) {
+                    if self.white[other] != white && !Self::is_king(self.pieces[other]) {
+                        push(to, Some(other));
+                    }
+                }
+            }
+        }
+    }
+
+    // squares the piece on `slot` could have come from with a quiet move
+    fn origins(&self, slot: usize) -> Vec<i32> {
+        let to = self.squares[slot];
+        let piece = self.pieces[slot];
+        let mut origins = vec![];
+        if Self::is_pawn(piece) {
+            let back = if self.white[slot] { -1 } else { 1 };
+            let double_row = if self.white[slot] { 4 } else { 3 };
+            if let Some(one) = offset(to, (0, back)) {
+                // pawns never stand on the first or last rank
+                if self.occupant(one).is_none() && (1..7).contains(&(one / 8)) {
+                    origins.push(one);
+                    if to / 8 == double_row {
+                        if let Some(two) = offset(one, (0, back)) {
+                            if self.occupant(two).is_none() {
+                                origins.push(two);
+                            }
+                        }
+                    }
+                }
+            }
+            return origins;
+        }
+        let (steps, sliding) = Self::steps(piece);
+        for &step in steps {
+            let mut square = to;
+            while let Some(next) = offset(square, step) {
+                if self.occupant(next).is_some() {
+                    break;
+                }
+                origins.push(next);
+                if !sliding {
+                    break;
+                }
+                square = next;
+            }
+        }
+        origins
+    }
+
+    fn board(&self) -> [ChessPiece; 64] {
+        let mut board = [ChessPiece::None; 64];
+        for slot in 0..self.pieces.len() {
+            if self.squares[slot] >= 0 {
+                board[self.squares[slot] as usize] = self.pieces[slot];
+            }
+        }
+        board
+    }
+
+    fn is_valid(&self, white_to_move: bool) -> bool {
+        let count = self.pieces.len();
+        for slot in 0..count {
+            let square = self.squares[slot];
+            if (0..slot).any(|other| self.squares[other] == square) {
+                return false;
+            }
+            if Self::is_pawn(self.pieces[slot]) && !(1..7).contains(&(square / 8)) {
+                return false;
+            }
+        }
+        // the side that just moved cannot have left its king in check
+        !self.king_attacked(!white_to_move)
+    }
+}
+
+const UNKNOWN: u8 = 0;
+const WIN: u8 = 1;
+const LOSS: u8 = 2;
+const DRAW: u8 = 3;
+const ILLEGAL: u8 = 4;
+
+// the finished tables, looked up for either color
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EndgameTables {
+    tables: HashMap<String, EndgameTable>,
+}
+
+impl EndgameTables {
+    // every table file in the directory
+    pub fn load_dir(directory: &str) -> Result<Self, String> {
+        let entries = fs::read_dir(directory).map_err(|err| format!("{}: {}", directory, err))?;
+        let mut tables = EndgameTables::default();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(ENDGAME_TABLE_EXTENSION) {
+                tables.insert(EndgameTable::load(&path.to_string_lossy())?);
+            }
+        }
+        Ok(tables)
+    }
+
+    pub fn insert(&mut self, table: EndgameTable) {
+        self.tables.insert(table.name.clone(), table);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EndgameTable> {
+        self.tables.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        self.tables.keys().collect()
+    }
+
+    // exact result for the side to move, None when no table covers the material
+    pub fn probe(&self, board: &[ChessPiece; 64], white_to_move: bool) -> Option<Dtm> {
+        let count = piece_count(board);
+        if count == 2 {
+            return Some(Dtm::Draw);
+        }
+        if count > MAX_ENDGAME_PIECES {
+            return None;
+        }
+        let (key, flipped_key) = material_keys(board);
+        if let Some(table) = self.tables.get(&key) {
+            return table.probe(board, white_to_move);
+        }
+        self.tables
+            .get(&flipped_key)?
+            .probe(&flip_board(board), !white_to_move)
+    }
+
+    pub fn probe_fen(&self, fen: &str) -> Option<Dtm> {
+        let white_to_move = fen.split(" ").nth(1) != Some("b");
+        self.probe(&fen_to_board(fen), white_to_move)
+    }
+
+    // the move that keeps the best result, with that result, for training and the engine
+    pub fn best_move(&self, game: &Game) -> Option<(PossibleMoves, Dtm)> {
+        let board = fen_to_board(&game.fen);
+        let white_to_move = game.fen.split(" ").nth(1) != Some("b");
+        self.probe(&board, white_to_move)?;
+        let color = if white_to_move {
+            Color::White
+        } else {
+            Color::Black
+        };
+        let mut best: Option<(PossibleMoves, Dtm)> = None;
+        for mv in game.possible_moves(color) {
+            let mut child = game.clone();
+            let result = if child.make_move(&to_move(game, &mv)).0 {
+                // the side to move field is gone from the fen once the game is over
+                self.probe(&fen_to_board(&child.fen), !white_to_move)?
+                    .parent()
+            } else if mv.captured_piece != ChessPiece::None && piece_count(&board) == 3 {
+                // make_move turns down captures that leave two bare kings, but the king
+                // still can't take a defended piece
+                let mut kings = board;
+                kings[mv.to as usize] = kings[mv.from as usize];
+                kings[mv.from as usize] = ChessPiece::None;
+                if in_check(&kings, color) {
+                    continue;
+                }
+                Dtm::Draw
+            } else {
+                continue;
+            };
+            let better = match &best {
+                Some((_, best_result)) => result.preference() > best_result.preference(),
+                None => true,
+            };
+            if better {
+                best = Some((mv, result));
+            }
+        }
+        best
+    }
+
+    // build a table and, first, every smaller table its captures and promotions lead to
+    pub fn generate(&mut self, name: &str) -> Result<(), String> {
+        let pieces = parse_material(name).ok_or(format!("cannot build a table for {}", name))?;
+        if self.tables.contains_key(name) || pieces.len() <= 2 {
+            return Ok(());
+        }
+        for child in child_materials(&pieces) {
+            let board = material_board(&child);
+            let (key, flipped_key) = material_keys(&board);
+            if !self.tables.contains_key(&key) && !self.tables.contains_key(&flipped_key) {
+                self.generate(&canonical_name(&child))?;
+            }
+        }
+        let table = generate_table(name, pieces, self)?;
+        self.insert(table);
+        Ok(())
+    }
+}
+
+// any board holding the material, only used to name it
+fn material_board(pieces: &[ChessPiece]) -> [ChessPiece; 64] {
+    let mut board = [ChessPiece::None; 64];
+    for (square, piece) in pieces.iter().enumerate() {
+        board[square] = *piece;
+    }
+    board
+}
+
+// stronger side first so each material is built once, whichever color has it
+fn canonical_name(pieces: &[ChessPiece]) -> String {
+    let value = |white: bool| -> i32 {
+        pieces
+            .iter()
+            .filter(|piece| is_white(**piece) == white)
+            .map(|piece| piece.piece_value())
+            .sum()
+    };
+    let (key, flipped_key) = material_keys(&material_board(pieces));
+    if value(true) >= value(false) {
+        key
+    } else {
+        flipped_key
+    }
+}
+
+// materials one capture or promotion away, bare kings left out
+fn child_materials(pieces: &[ChessPiece]) -> Vec<Vec<ChessPiece>> {
+    let mut children = vec![];
+    for (slot, piece) in pieces.iter().enumerate() {
+        if Placement::is_king(*piece) {
+            continue;
+        }
+        let mut captured = pieces.to_vec();
+        captured.remove(slot);
+        if captured.len() > 2 {
+            children.push(captured);
+        }
+        if Placement::is_pawn(*piece) {
+            for promotion in PROMOTIONS {
+                let mut promoted = pieces.to_vec();
+                promoted[slot] = if is_white(*piece) {
+                    promotion
+                } else {
+                    swap_color(promotion)
+                };
+                children.push(promoted);
+            }
+        }
+    }
+    children
+}
+
+// retrograde analysis: mates are found first, then every position that can reach a
+// lost position is won one ply later, and a position whose moves all reach won
+// positions is lost one ply after the slowest of them
+fn generate_table(
+    name: &str,
+    pieces: Vec<ChessPiece>,
+    tables: &EndgameTables,
+) -> Result<EndgameTable, String> {
+    let count = pieces.len();
+    let size = 2 << (6 * count);
+    let white = pieces
+        .iter()
+        .map(|piece| is_white(*piece))
+        .collect::<Vec<bool>>();
+    let placement = |squares| Placement {
+        pieces: &pieces,
+        white: &white,
+        squares,
+    };
+
+    let mut state = vec![UNKNOWN; size];
+    let mut plies = vec![0u8; size];
+    // moves that stay in the table and are not known to lose yet
+    let mut moves_left = vec![0u8; size];
+    // longest win the opponent gets by leaving the table, in plies
+    let mut exit_max = vec![0u8; size];
+    let mut can_lose = vec![true; size];
+    let mut pending_wins: Vec<Vec<usize>> = vec![];
+    let mut pending_losses: Vec<Vec<usize>> = vec![];
+    let schedule = |pending: &mut Vec<Vec<usize>>, ply: usize, index: usize| {
+        if pending.len() <= ply {
+            pending.resize(ply + 1, vec![]);
+        }
+        pending[ply].push(index);
+    };
+
+    for index in 0..size {
+        let (squares, white_to_move) = decode_index(index, count);
+        let position = placement(squares);
+        if !position.is_valid(white_to_move) {
+            state[index] = ILLEGAL;
+            continue;
+        }
+
+        let mut legal_moves = 0;
+        for (slot, to, captured, promotion) in position.moves(white_to_move) {
+            let mut child = position;
+            child.squares[slot] = to;
+            if let Some(captured) = captured {
+                child.squares[captured] = -1;
+            }
+            if child.king_attacked(white_to_move) {
+                continue;
+            }
+            legal_moves += 1;
+
+            if captured.is_none() && promotion.is_none() {
+                moves_left[index] += 1;
+                continue;
+            }
+            // the move leaves the table, the smaller table already knows the result
+            let mut board = child.board();
+            if let Some(promotion) = promotion {
+                board[to as usize] = promotion;
+            }
+            let result = tables.probe(&board, !white_to_move).ok_or(format!(
+                "{} needs the table for {:?}",
+                name,
+                material_keys(&board).0
+            ))?;
+            match result {
+                Dtm::Loss(_) => {
+                    can_lose[index] = false;
+                    schedule(&mut pending_wins, result.parent().plies() as usize, index);
+                }
+                Dtm::Win(_) => exit_max[index] = exit_max[index].max(result.plies() as u8),
+                Dtm::Draw => can_lose[index] = false,
+            }
+        }
+
+        if legal_moves == 0 {
+            if position.king_attacked(white_to_move) {
+                schedule(&mut pending_losses, 0, index);
+            } else {
+                state[index] = DRAW;
+            }
+        } else if moves_left[index] == 0 && can_lose[index] {
+            schedule(&mut pending_losses, exit_max[index] as usize + 1, index);
+        }
+    }
+
+    let mut ply = 0;
+    while ply < pending_wins.len().max(pending_losses.len()) {
+        let mut frontier = vec![];
+        for (pending, result) in [(&mut pending_wins, WIN), (&mut pending_losses, LOSS)] {
+            if let Some(indices) = pending.get_mut(ply) {
+                for index in std::mem::take(indices) {
+                    if state[index] == UNKNOWN {
+                        state[index] = result;
+                        plies[index] = ply as u8;
+                        frontier.push(index);
+                    }
+                }
+            }
+        }
+
+        for index in frontier {
+            let (squares, white_to_move) = decode_index(index, count);
+            let position = placement(squares);
+            // the side that is not to move made the last move
+            for slot in 0..count {
+                if white[slot] == white_to_move {
+                    continue;
+                }
+                for origin in position.origins(slot) {
+                    let mut parent = squares;
+                    parent[slot] = origin;
+                    let parent_index = position_index(&parent[..count], !white_to_move);
+                    if state[parent_index] != UNKNOWN {
+                        continue;
+                    }
+                    if state[index] == LOSS {
+                        schedule(&mut pending_wins, ply + 1, parent_index);
+                    } else {
+                        moves_left[parent_index] -= 1;
+                        if moves_left[parent_index] == 0 && can_lose[parent_index] {
+                            let lost_at = ply.max(exit_max[parent_index] as usize) + 1;
+                            schedule(&mut pending_losses, lost_at, parent_index);
+                        }
+                    }
+                }
+            }
+        }
+        ply += 1;
+    }
+
+    let values = (0..size)
+        .map(|index| match state[index] {
+            WIN => Dtm::Win(plies[index].div_ceil(2)).to_byte(),
+            LOSS => Dtm::Loss(plies[index] / 2).to_byte(),
+            _ => Dtm::Draw.to_byte(),
+        })
+        .collect();
+    Ok(EndgameTable {
+        name: name.to_string(),
+        pieces,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_kqk() {
+        let mut tables = EndgameTables::default();
+        tables.generate("KQvK").unwrap();
+        let table = tables.get("KQvK").unwrap();
+        // the longest king and queen mate takes ten moves
+        assert_eq!(table.longest_mate(), 10);
+
+        assert_eq!(
+            tables.probe_fen("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            tables.probe_fen("Q6k/8/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Loss(0))
+        );
+        // stalemate
+        assert_eq!(
+            tables.probe_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Some(Dtm::Draw)
+        );
+        // the same mate with the colors swapped is found through the flipped table
+        assert_eq!(
+            tables.probe_fen("1q6/8/8/8/8/6k1/8/7K b - - 0 1"),
+            Some(Dtm::Win(1))
+        );
+
+        let loaded = EndgameTable::from_bytes(&table.to_bytes()).unwrap();
+        assert_eq!(&loaded, table);
+
+        let board = fen_to_board("8/8/4k3/8/8/3PK3/8/6RQ w - - 0 1");
+        assert_eq!(
+            material_keys(&board),
+            (String::from("KQRPvK"), String::from("KvKQRP"))
+        );
+        assert_eq!(piece_count(&board), 5);
+
+        // the queen is defended, so taking it is no way out of the check
+        let game = Game::new("4k3/3Q4/2K5/8/8/8/8/8 b - - 0 1", None);
+        let (mv, result) = tables.best_move(&game).unwrap();
+        assert_eq!((mv.from, mv.to), (4, 5));
+        assert!(matches!(result, Dtm::Loss(_)));
+    }
+}
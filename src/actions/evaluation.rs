@@ -0,0 +1,819 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::interface::chessboard::piece::{ChessPiece, Color};
+
+use super::{
+    attacks::{attack_map, piece_color},
+    heatmap::{blend_heat, game_phase, heat, piece_kind, MAX_PHASE, PIECE_KINDS},
+    movegen::piece_targets,
+    params::EvalParams,
+    pawns::pawn_structure,
+    square::{File, Rank, Square, ALL_WAYS},
+};
+
+// centipawn value of each piece, the king is never traded so it has none
+pub const PAWN_VALUE: i32 = 100;
+pub const KNIGHT_VALUE: i32 = 320;
+pub const BISHOP_VALUE: i32 = 330;
+pub const ROOK_VALUE: i32 = 500;
+pub const QUEEN_VALUE: i32 = 900;
+
+pub const BISHOP_PAIR_BONUS: i32 = 30;
+
+// knights get better and rooks worse the more pawns are left on the board
+pub const KNIGHT_PAWN_ADJUSTMENT: i32 = 6;
+pub const ROOK_PAWN_ADJUSTMENT: i32 = -12;
+// pawn count the knight and rook values above are measured against
+const IMBALANCE_PAWN_BASE: i32 = 5;
+// two rooks, or a queen and rook, partly do the same job
+pub const ROOK_PAIR_PENALTY: i32 = -16;
+pub const QUEEN_ROOK_PENALTY: i32 = -8;
+
+// share of a piece's value lost when it can be captured right away
+const THREAT_DIVISOR: i32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialParams {
+    pub pawn: i32,
+    pub knight: i32,
+    pub bishop: i32,
+    pub rook: i32,
+    pub queen: i32,
+    pub bishop_pair: i32,
+    pub knight_pawn_adjustment: i32,
+    pub rook_pawn_adjustment: i32,
+    pub imbalance_pawn_base: i32,
+    pub rook_pair: i32,
+    pub queen_rook: i32,
+    pub threat_divisor: i32,
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        MaterialParams {
+            pawn: PAWN_VALUE,
+            knight: KNIGHT_VALUE,
+            bishop: BISHOP_VALUE,
+            rook: ROOK_VALUE,
+            queen: QUEEN_VALUE,
+            bishop_pair: BISHOP_PAIR_BONUS,
+            knight_pawn_adjustment: KNIGHT_PAWN_ADJUSTMENT,
+            rook_pawn_adjustment: ROOK_PAWN_ADJUSTMENT,
+            imbalance_pawn_base: IMBALANCE_PAWN_BASE,
+            rook_pair: ROOK_PAIR_PENALTY,
+            queen_rook: QUEEN_ROOK_PENALTY,
+            threat_divisor: THREAT_DIVISOR,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MaterialCount {
+    pub pawns: i32,
+    pub knights: i32,
+    pub bishops: i32,
+    pub rooks: i32,
+    pub queens: i32,
+}
+
+pub fn material_value(params: &MaterialParams, piece: ChessPiece) -> i32 {
+    match piece {
+        ChessPiece::WPawn | ChessPiece::BPawn => params.pawn,
+        ChessPiece::WKnight | ChessPiece::BKnight => params.knight,
+        ChessPiece::WBishop | ChessPiece::BBishop => params.bishop,
+        ChessPiece::WRook | ChessPiece::BRook => params.rook,
+        ChessPiece::WQueen | ChessPiece::BQueen => params.queen,
+        _ => 0,
+    }
+}
+
+// what a piece that is en prise is marked down by, kings are left to the search
+pub fn threat_value(params: &MaterialParams, piece: ChessPiece) -> i32 {
+    material_value(params, piece) / params.threat_divisor.max(1)
+}
+
+pub fn count_material(board: &[ChessPiece; 64], color: Color) -> MaterialCount {
+    let mut count = MaterialCount::default();
+    for piece in board.iter() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => count.pawns += 1,
+            ChessPiece::WKnight | ChessPiece::BKnight => count.knights += 1,
+            ChessPiece::WBishop | ChessPiece::BBishop => count.bishops += 1,
+            ChessPiece::WRook | ChessPiece::BRook => count.rooks += 1,
+            ChessPiece::WQueen | ChessPiece::BQueen => count.queens += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
+// plain sum of the piece values
+pub fn material_sum(params: &MaterialParams, count: &MaterialCount) -> i32 {
+    count.pawns * params.pawn
+        + count.knights * params.knight
+        + count.bishops * params.bishop
+        + count.rooks * params.rook
+        + count.queens * params.queen
+}
+
+// corrections to the plain sum that depend on which pieces are together on the board
+pub fn imbalance(params: &MaterialParams, count: &MaterialCount) -> i32 {
+    let mut score = 0;
+    if count.bishops >= 2 {
+        score += params.bishop_pair;
+    }
+    let pawn_diff = count.pawns - params.imbalance_pawn_base;
+    score += count.knights * pawn_diff * params.knight_pawn_adjustment;
+    score += count.rooks * pawn_diff * params.rook_pawn_adjustment;
+    if count.rooks >= 2 {
+        score += params.rook_pair;
+    }
+    if count.queens >= 1 && count.rooks >= 1 {
+        score += params.queen_rook;
+    }
+    score
+}
+
+// material balance in centipawns from the point of view of `color`
+pub fn material_score(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> i32 {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let own = count_material(board, color);
+    let other = count_material(board, opponent);
+
+    let params = &params.material;
+    material_sum(params, &own) + imbalance(params, &own)
+        - material_sum(params, &other)
+        - imbalance(params, &other)
+}
+
+// king safety, middle game centipawns
+pub const PAWN_SHIELD_BONUS: [i32; 2] = [12, 6];
+// enemy pawn on a king file, indexed by how many rows in front of the king it stands
+pub const PAWN_STORM_PENALTY: [i32; 5] = [0, 0, -20, -10, -5];
+pub const OPEN_FILE_NEAR_KING: i32 = -25;
+pub const HALF_OPEN_FILE_NEAR_KING: i32 = -12;
+pub const KNIGHT_ATTACK_UNITS: i32 = 2;
+pub const BISHOP_ATTACK_UNITS: i32 = 2;
+pub const ROOK_ATTACK_UNITS: i32 = 3;
+pub const QUEEN_ATTACK_UNITS: i32 = 5;
+// penalty by attack units, grows faster than linear since attacks on the king add up
+pub const KING_ATTACK_PENALTY: [i32; 16] = [
+    0, 0, 2, 4, 8, 14, 22, 32, 44, 58, 74, 92, 112, 134, 158, 184,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KingSafetyParams {
+    pub pawn_shield: [i32; 2],
+    pub pawn_storm: [i32; 5],
+    pub open_file: i32,
+    pub half_open_file: i32,
+    pub knight_attack_units: i32,
+    pub bishop_attack_units: i32,
+    pub rook_attack_units: i32,
+    pub queen_attack_units: i32,
+    pub attack_penalty: [i32; 16],
+}
+
+impl Default for KingSafetyParams {
+    fn default() -> Self {
+        KingSafetyParams {
+            pawn_shield: PAWN_SHIELD_BONUS,
+            pawn_storm: PAWN_STORM_PENALTY,
+            open_file: OPEN_FILE_NEAR_KING,
+            half_open_file: HALF_OPEN_FILE_NEAR_KING,
+            knight_attack_units: KNIGHT_ATTACK_UNITS,
+            bishop_attack_units: BISHOP_ATTACK_UNITS,
+            rook_attack_units: ROOK_ATTACK_UNITS,
+            queen_attack_units: QUEEN_ATTACK_UNITS,
+            attack_penalty: KING_ATTACK_PENALTY,
+        }
+    }
+}
+
+// squares an enemy piece hits when it attacks the king, the king square included, one bit
+// per square
+fn king_zone(king: Square, color: Color) -> u64 {
+    let mut zone = 1 << king.index();
+    for (files, ranks) in ALL_WAYS {
+        if let Some(square) = king.offset(files, ranks) {
+            zone |= 1 << square.index();
+        }
+    }
+    // one more rank in front of the king, where the shield pawns live
+    let forward = if color == Color::White { 1 } else { -1 };
+    for files in -1..=1 {
+        if let Some(square) = king.offset(files, 2 * forward) {
+            zone |= 1 << square.index();
+        }
+    }
+    zone
+}
+
+// squares a knight, bishop, rook or queen can go to, empty or holding an enemy piece, one
+// bit per square
+fn attacked_squares(board: &[ChessPiece; 64], piece: ChessPiece, position: i32) -> u64 {
+    let mut squares = 0;
+    if let Some(from) = Square::new(position) {
+        piece_targets(board, piece, from, |to, target| {
+            if piece_color(target) != piece_color(piece) {
+                squares |= 1 << to.index();
+            }
+        });
+    }
+    squares
+}
+
+// pawn shield, pawn storm, open files and piece attacks around the king of `color`
+pub fn king_safety(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> i32 {
+    let params = &params.king_safety;
+    let (king, own_pawn, enemy_pawn) = if color == Color::White {
+        (ChessPiece::WKing, ChessPiece::WPawn, ChessPiece::BPawn)
+    } else {
+        (ChessPiece::BKing, ChessPiece::BPawn, ChessPiece::WPawn)
+    };
+    let king_square = match board
+        .iter()
+        .position(|piece| *piece == king)
+        .and_then(|pos| Square::new(pos as i32))
+    {
+        Some(square) => square,
+        None => return 0,
+    };
+    let king_rank = king_square.rank().index();
+    let forward = if color == Color::White { 1 } else { -1 };
+    let castled = (king_rank - Rank::back(color).index()).abs() <= 1;
+
+    let mut score = 0;
+    for files in -1..=1 {
+        let file = match File::new(king_square.file().index() + files) {
+            Some(file) => file,
+            None => continue,
+        };
+        let mut own_pawns = 0;
+        let mut enemy_pawns = 0;
+        for rank in Rank::ALL {
+            let piece = board[Square::at(file, rank).index() as usize];
+            // ranks in front of the king count up from 1
+            let distance = (rank.index() - king_rank) * forward;
+            if piece == own_pawn {
+                own_pawns += 1;
+                if castled && (1..=2).contains(&distance) {
+                    score += params.pawn_shield[(distance - 1) as usize];
+                }
+            } else if piece == enemy_pawn {
+                enemy_pawns += 1;
+                if castled && (1..=4).contains(&distance) {
+                    score += params.pawn_storm[distance as usize];
+                }
+            }
+        }
+        if own_pawns == 0 && enemy_pawns == 0 {
+            score += params.open_file;
+        } else if own_pawns == 0 {
+            score += params.half_open_file;
+        }
+    }
+
+    let zone = king_zone(king_square, color);
+    let mut attack_units = 0;
+    let mut attackers = 0;
+    for (position, piece) in board.iter().enumerate() {
+        if !piece_color(*piece).is_some_and(|piece_color| piece_color != color) {
+            continue;
+        }
+        let units = match piece {
+            ChessPiece::WKnight | ChessPiece::BKnight => params.knight_attack_units,
+            ChessPiece::WBishop | ChessPiece::BBishop => params.bishop_attack_units,
+            ChessPiece::WRook | ChessPiece::BRook => params.rook_attack_units,
+            ChessPiece::WQueen | ChessPiece::BQueen => params.queen_attack_units,
+            _ => continue,
+        };
+        let hits = (attacked_squares(board, *piece, position as i32) & zone).count_ones() as i32;
+        if hits > 0 {
+            attackers += 1;
+            attack_units += units * hits;
+        }
+    }
+    // a single attacker is rarely dangerous on its own
+    if attackers >= 2 {
+        let index = (attack_units as usize).min(params.attack_penalty.len() - 1);
+        score -= params.attack_penalty[index];
+    }
+    score
+}
+
+// king safety of `color`, only weighs in while pieces are on the board
+pub fn tapered_king_safety(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> i32 {
+    king_safety(params, board, color) * phase.clamp(0, MAX_PHASE) / MAX_PHASE
+}
+
+// king safety of `color` against the opponent's
+pub fn king_safety_score(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> i32 {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    tapered_king_safety(params, board, color, phase)
+        - tapered_king_safety(params, board, opponent, phase)
+}
+
+// mobility, (middle game, end game) centipawns per safe square above the baseline count
+pub const KNIGHT_MOBILITY: (i32, i32) = (4, 4);
+pub const BISHOP_MOBILITY: (i32, i32) = (5, 5);
+pub const ROOK_MOBILITY: (i32, i32) = (2, 4);
+pub const QUEEN_MOBILITY: (i32, i32) = (1, 2);
+// safe squares a piece typically has, fewer than this counts against it
+const KNIGHT_MOBILITY_BASE: i32 = 4;
+const BISHOP_MOBILITY_BASE: i32 = 6;
+const ROOK_MOBILITY_BASE: i32 = 7;
+const QUEEN_MOBILITY_BASE: i32 = 13;
+pub const KNIGHT_OUTPOST: (i32, i32) = (20, 10);
+pub const BISHOP_OUTPOST: (i32, i32) = (10, 5);
+pub const ROOK_OPEN_FILE: (i32, i32) = (25, 10);
+pub const ROOK_HALF_OPEN_FILE: (i32, i32) = (12, 6);
+pub const ROOK_SEVENTH_RANK: (i32, i32) = (20, 30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MobilityParams {
+    pub knight: (i32, i32),
+    pub bishop: (i32, i32),
+    pub rook: (i32, i32),
+    pub queen: (i32, i32),
+    pub knight_base: i32,
+    pub bishop_base: i32,
+    pub rook_base: i32,
+    pub queen_base: i32,
+    pub knight_outpost: (i32, i32),
+    pub bishop_outpost: (i32, i32),
+    pub rook_open_file: (i32, i32),
+    pub rook_half_open_file: (i32, i32),
+    pub rook_seventh_rank: (i32, i32),
+}
+
+impl Default for MobilityParams {
+    fn default() -> Self {
+        MobilityParams {
+            knight: KNIGHT_MOBILITY,
+            bishop: BISHOP_MOBILITY,
+            rook: ROOK_MOBILITY,
+            queen: QUEEN_MOBILITY,
+            knight_base: KNIGHT_MOBILITY_BASE,
+            bishop_base: BISHOP_MOBILITY_BASE,
+            rook_base: ROOK_MOBILITY_BASE,
+            queen_base: QUEEN_MOBILITY_BASE,
+            knight_outpost: KNIGHT_OUTPOST,
+            bishop_outpost: BISHOP_OUTPOST,
+            rook_open_file: ROOK_OPEN_FILE,
+            rook_half_open_file: ROOK_HALF_OPEN_FILE,
+            rook_seventh_rank: ROOK_SEVENTH_RANK,
+        }
+    }
+}
+
+// squares attacked by the pawns of `color`
+fn pawn_attack_map(board: &[ChessPiece; 64], color: Color) -> u64 {
+    let pawn = if color == Color::White {
+        ChessPiece::WPawn
+    } else {
+        ChessPiece::BPawn
+    };
+    let forward = if color == Color::White { 1 } else { -1 };
+    let mut attacks = 0;
+    for (position, piece) in board.iter().enumerate() {
+        if *piece != pawn {
+            continue;
+        }
+        let square = match Square::new(position as i32) {
+            Some(square) => square,
+            None => continue,
+        };
+        for files in [-1, 1] {
+            if let Some(target) = square.offset(files, forward) {
+                attacks |= 1 << target.index();
+            }
+        }
+    }
+    attacks
+}
+
+// a square on the opponent's half, guarded by a pawn, that no enemy pawn can ever attack
+fn is_outpost(board: &[ChessPiece; 64], position: i32, color: Color) -> bool {
+    let square = match Square::new(position) {
+        Some(square) => square,
+        None => return false,
+    };
+    let rank = square.rank().index();
+    let (relative_rank, enemy_pawn) = if color == Color::White {
+        (rank, ChessPiece::BPawn)
+    } else {
+        (7 - rank, ChessPiece::WPawn)
+    };
+    if !(3..=5).contains(&relative_rank) {
+        return false;
+    }
+    if pawn_attack_map(board, color) & (1 << position) == 0 {
+        return false;
+    }
+    for r in Rank::ALL {
+        let ahead = if color == Color::White {
+            r > square.rank()
+        } else {
+            r < square.rank()
+        };
+        if !ahead {
+            continue;
+        }
+        for files in [-1, 1] {
+            let file = match File::new(square.file().index() + files) {
+                Some(file) => file,
+                None => continue,
+            };
+            if board[Square::at(file, r).index() as usize] == enemy_pawn {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+fn add_term(score: &mut (i32, i32), term: (i32, i32), times: i32) {
+    score.0 += term.0 * times;
+    score.1 += term.1 * times;
+}
+
+// mobility and activity of the pieces of `color` in (middle game, end game) centipawns
+pub fn piece_activity(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> (i32, i32) {
+    let params = &params.mobility;
+    let (own_pawn, enemy_pawn, enemy_king) = if color == Color::White {
+        (ChessPiece::WPawn, ChessPiece::BPawn, ChessPiece::BKing)
+    } else {
+        (ChessPiece::BPawn, ChessPiece::WPawn, ChessPiece::WKing)
+    };
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let enemy_pawn_attacks = pawn_attack_map(board, opponent);
+    // the opponent's pawn start and back ranks
+    let seventh_rank = Rank::pawn_start(opponent);
+    let eighth_rank = Rank::back(opponent);
+
+    let mut score = (0, 0);
+    for (position, piece) in board.iter().enumerate() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        let square = match Square::new(position as i32) {
+            Some(square) => square,
+            None => continue,
+        };
+        let position = square.index();
+        let (weight, base) = match piece {
+            ChessPiece::WKnight | ChessPiece::BKnight => (params.knight, params.knight_base),
+            ChessPiece::WBishop | ChessPiece::BBishop => (params.bishop, params.bishop_base),
+            ChessPiece::WRook | ChessPiece::BRook => (params.rook, params.rook_base),
+            ChessPiece::WQueen | ChessPiece::BQueen => (params.queen, params.queen_base),
+            _ => continue,
+        };
+        let safe_squares =
+            (attacked_squares(board, *piece, position) & !enemy_pawn_attacks).count_ones() as i32;
+        add_term(&mut score, weight, safe_squares - base);
+
+        match piece {
+            ChessPiece::WKnight | ChessPiece::BKnight if is_outpost(board, position, color) => {
+                add_term(&mut score, params.knight_outpost, 1);
+            }
+            ChessPiece::WBishop | ChessPiece::BBishop if is_outpost(board, position, color) => {
+                add_term(&mut score, params.bishop_outpost, 1);
+            }
+            ChessPiece::WRook | ChessPiece::BRook => {
+                let file_pieces = Rank::ALL
+                    .into_iter()
+                    .map(|rank| board[Square::at(square.file(), rank).index() as usize]);
+                let own_pawns = file_pieces.clone().filter(|p| *p == own_pawn).count();
+                let enemy_pawns = file_pieces.filter(|p| *p == enemy_pawn).count();
+                if own_pawns == 0 && enemy_pawns == 0 {
+                    add_term(&mut score, params.rook_open_file, 1);
+                } else if own_pawns == 0 {
+                    add_term(&mut score, params.rook_half_open_file, 1);
+                }
+
+                // only worth it while there are pawns to eat or the king is cut off there
+                if square.rank() == seventh_rank {
+                    let pawns_on_seventh = File::ALL.iter().any(|&file| {
+                        board[Square::at(file, seventh_rank).index() as usize] == enemy_pawn
+                    });
+                    let king_on_eighth = File::ALL.iter().any(|&file| {
+                        board[Square::at(file, eighth_rank).index() as usize] == enemy_king
+                    });
+                    if pawns_on_seventh || king_on_eighth {
+                        add_term(&mut score, params.rook_seventh_rank, 1);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    score
+}
+
+// piece activity of `color`, blended by the game phase
+pub fn mobility(params: &EvalParams, board: &[ChessPiece; 64], color: Color, phase: i32) -> i32 {
+    let (mg, eg) = piece_activity(params, board, color);
+    let phase = phase.clamp(0, MAX_PHASE);
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+// piece activity of `color` against the opponent's
+pub fn mobility_score(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> i32 {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    mobility(params, board, color, phase) - mobility(params, board, opponent, phase)
+}
+
+// pieces of the opponent that `color` can take right away
+pub fn threats(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> i32 {
+    let own_attacks = attack_map(board, color);
+    board
+        .iter()
+        .enumerate()
+        .filter(|(position, piece)| {
+            piece_color(**piece).is_some_and(|piece_color| piece_color != color)
+                && own_attacks & (1 << position) != 0
+        })
+        .map(|(_, piece)| threat_value(&params.material, *piece))
+        .sum()
+}
+
+// threats of `color` against the opponent's
+pub fn threat_score(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> i32 {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    threats(params, board, color) - threats(params, board, opponent)
+}
+
+// heat map contribution of each kind of piece
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PieceSquareTrace {
+    pub pawns: i32,
+    pub knights: i32,
+    pub bishops: i32,
+    pub rooks: i32,
+    pub queens: i32,
+    pub king: i32,
+}
+
+impl PieceSquareTrace {
+    pub fn total(&self) -> i32 {
+        self.pawns + self.knights + self.bishops + self.rooks + self.queens + self.king
+    }
+}
+
+// every evaluation term of one side, in centipawns good for that side
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SideTrace {
+    pub material: i32,
+    pub imbalance: i32,
+    pub piece_square: PieceSquareTrace,
+    pub pawn_structure: i32,
+    pub king_safety: i32,
+    pub mobility: i32,
+    pub threats: i32,
+}
+
+impl SideTrace {
+    pub fn total(&self) -> i32 {
+        self.material
+            + self.imbalance
+            + self.piece_square.total()
+            + self.pawn_structure
+            + self.king_safety
+            + self.mobility
+            + self.threats
+    }
+}
+
+// breakdown of a position's score, `score` is seen from the side it was asked for
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EvalTrace {
+    pub phase: i32,
+    pub white: SideTrace,
+    pub black: SideTrace,
+    pub score: i32,
+}
+
+fn trace_side(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> SideTrace {
+    let count = count_material(board, color);
+    // each kind's heat is summed before it is blended, as make_move keeps it for evaluate
+    let mut sums = [[0; 3]; PIECE_KINDS];
+    for (position, piece) in board.iter().enumerate() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        if let Some(kind) = piece_kind(*piece) {
+            let values = heat(&params.piece_square, *piece, color, position as i32);
+            for (sum, value) in sums[kind].iter_mut().zip(values) {
+                *sum += value;
+            }
+        }
+    }
+    let [pawns, knights, bishops, rooks, queens, king] = sums.map(|sum| blend_heat(sum, phase));
+    let piece_square = PieceSquareTrace {
+        pawns,
+        knights,
+        bishops,
+        rooks,
+        queens,
+        king,
+    };
+
+    SideTrace {
+        material: material_sum(&params.material, &count),
+        imbalance: imbalance(&params.material, &count),
+        piece_square,
+        pawn_structure: pawn_structure(params, board, color, phase),
+        king_safety: tapered_king_safety(params, board, color, phase),
+        mobility: mobility(params, board, color, phase),
+        threats: threats(params, board, color),
+    }
+}
+
+// evaluate the board term by term, the score is the sum of the side totals
+pub fn trace_board(params: &EvalParams, board: &[ChessPiece; 64], color: Color) -> EvalTrace {
+    let phase = game_phase(board);
+    let white = trace_side(params, board, Color::White, phase);
+    let black = trace_side(params, board, Color::Black, phase);
+    let white_score = white.total() - black.total();
+    EvalTrace {
+        phase,
+        score: if color == Color::White {
+            white_score
+        } else {
+            -white_score
+        },
+        white,
+        black,
+    }
+}
+
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = [
+            ("material", self.white.material, self.black.material),
+            ("imbalance", self.white.imbalance, self.black.imbalance),
+            (
+                "pawn squares",
+                self.white.piece_square.pawns,
+                self.black.piece_square.pawns,
+            ),
+            (
+                "knight squares",
+                self.white.piece_square.knights,
+                self.black.piece_square.knights,
+            ),
+            (
+                "bishop squares",
+                self.white.piece_square.bishops,
+                self.black.piece_square.bishops,
+            ),
+            (
+                "rook squares",
+                self.white.piece_square.rooks,
+                self.black.piece_square.rooks,
+            ),
+            (
+                "queen squares",
+                self.white.piece_square.queens,
+                self.black.piece_square.queens,
+            ),
+            (
+                "king square",
+                self.white.piece_square.king,
+                self.black.piece_square.king,
+            ),
+            (
+                "pawn structure",
+                self.white.pawn_structure,
+                self.black.pawn_structure,
+            ),
+            (
+                "king safety",
+                self.white.king_safety,
+                self.black.king_safety,
+            ),
+            ("mobility", self.white.mobility, self.black.mobility),
+            ("threats", self.white.threats, self.black.threats),
+            ("total", self.white.total(), self.black.total()),
+        ];
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "term", "white", "black", "diff")?;
+        for (name, white, black) in rows {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                name,
+                white,
+                black,
+                white - black
+            )?;
+        }
+        writeln!(f, "phase {} / {}", self.phase, MAX_PHASE)?;
+        write!(f, "score {}", self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::chessboard::piece::fen_to_board;
+
+    #[test]
+    fn test_material_score() {
+        let params = EvalParams::default();
+        let board = fen_to_board("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(material_score(&params, &board, Color::White), 0);
+
+        // white is a bishop up and has the bishop pair
+        let board = fen_to_board("4k3/pppp4/2bn4/8/8/8/PPPP4/1NB1KB2 w - - 0 1");
+        assert_eq!(
+            material_score(&params, &board, Color::White),
+            BISHOP_VALUE + BISHOP_PAIR_BONUS
+        );
+        assert_eq!(
+            material_score(&params, &board, Color::Black),
+            -material_score(&params, &board, Color::White)
+        );
+    }
+
+    #[test]
+    fn test_piece_activity() {
+        let params = EvalParams::default();
+        // rook on the open e file and the seventh rank, knight on a d5 outpost
+        let board = fen_to_board("6k1/1p2Rp1p/8/2pN4/2P5/8/5PPP/6K1 w - - 0 1");
+        let board_closed = fen_to_board("6k1/1p3p1p/8/2p5/2P5/8/4RPPP/1N4K1 w - - 0 1");
+        let d5 = Square::from_algebraic("d5").unwrap().index();
+        assert!(is_outpost(&board, d5, Color::White));
+        assert!(
+            piece_activity(&params, &board, Color::White).0
+                > piece_activity(&params, &board_closed, Color::White).0
+        );
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let params = EvalParams::default();
+        // castled king behind its pawns against one with the shield pushed away
+        let sheltered = fen_to_board("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let exposed = fen_to_board("6k1/5ppp/8/8/8/5PPP/8/6K1 w - - 0 1");
+        assert!(
+            king_safety(&params, &sheltered, Color::White)
+                > king_safety(&params, &exposed, Color::White)
+        );
+
+        // queen and rook both hitting the squares around the king
+        let attacked = fen_to_board("6k1/5ppp/8/8/8/6q1/5PP1/3r2K1 w - - 0 1");
+        assert!(
+            king_safety(&params, &attacked, Color::White)
+                < king_safety(&params, &sheltered, Color::White)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use crate::interface::chessboard::piece::{ChessPiece, Color as PieceColor};
 
 #[derive(PartialEq)]
@@ -7,6 +9,142 @@ pub enum GameType {
     End,
 }
 
+// phase weight of each non-pawn piece, a full set of pieces adds up to MAX_PHASE
+pub const KNIGHT_PHASE: i32 = 1;
+pub const BISHOP_PHASE: i32 = 1;
+pub const ROOK_PHASE: i32 = 2;
+pub const QUEEN_PHASE: i32 = 4;
+pub const MAX_PHASE: i32 = 24;
+// phase at which the middle game tables apply on their own
+pub const MIDDLE_PHASE: i32 = 12;
+
+// how much non-pawn material is left, from MAX_PHASE (opening) down to 0 (bare endgame)
+pub fn game_phase(board: &[ChessPiece; 64]) -> i32 {
+    let phase: i32 = board
+        .iter()
+        .map(|piece| match piece {
+            ChessPiece::WKnight | ChessPiece::BKnight => KNIGHT_PHASE,
+            ChessPiece::WBishop | ChessPiece::BBishop => BISHOP_PHASE,
+            ChessPiece::WRook | ChessPiece::BRook => ROOK_PHASE,
+            ChessPiece::WQueen | ChessPiece::BQueen => QUEEN_PHASE,
+            _ => 0,
+        })
+        .sum();
+    phase.min(MAX_PHASE)
+}
+
+// heat map of one kind of piece for each stage of the game, written from black's side
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseTables {
+    pub opening: [[i32; 8]; 8],
+    pub middle: [[i32; 8]; 8],
+    pub endgame: [[i32; 8]; 8],
+}
+
+impl PhaseTables {
+    fn from_heat_map(piece: ChessPiece) -> Self {
+        PhaseTables {
+            opening: piece_heat_map(piece, PieceColor::Black, &GameType::Opening),
+            middle: piece_heat_map(piece, PieceColor::Black, &GameType::Middle),
+            endgame: piece_heat_map(piece, PieceColor::Black, &GameType::End),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceSquareTables {
+    pub pawn: PhaseTables,
+    pub knight: PhaseTables,
+    pub bishop: PhaseTables,
+    pub rook: PhaseTables,
+    pub queen: PhaseTables,
+    pub king: PhaseTables,
+}
+
+impl PieceSquareTables {
+    pub fn for_piece(&self, piece: ChessPiece) -> Option<&PhaseTables> {
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => Some(&self.pawn),
+            ChessPiece::WKnight | ChessPiece::BKnight => Some(&self.knight),
+            ChessPiece::WBishop | ChessPiece::BBishop => Some(&self.bishop),
+            ChessPiece::WRook | ChessPiece::BRook => Some(&self.rook),
+            ChessPiece::WQueen | ChessPiece::BQueen => Some(&self.queen),
+            ChessPiece::WKing | ChessPiece::BKing => Some(&self.king),
+            _ => None,
+        }
+    }
+}
+
+// where a piece's heat map sums are kept: pawn, knight, bishop, rook, queen and king
+pub const PIECE_KINDS: usize = 6;
+
+pub fn piece_kind(piece: ChessPiece) -> Option<usize> {
+    match piece {
+        ChessPiece::WPawn | ChessPiece::BPawn => Some(0),
+        ChessPiece::WKnight | ChessPiece::BKnight => Some(1),
+        ChessPiece::WBishop | ChessPiece::BBishop => Some(2),
+        ChessPiece::WRook | ChessPiece::BRook => Some(3),
+        ChessPiece::WQueen | ChessPiece::BQueen => Some(4),
+        ChessPiece::WKing | ChessPiece::BKing => Some(5),
+        _ => None,
+    }
+}
+
+// the hand written tables below
+impl Default for PieceSquareTables {
+    fn default() -> Self {
+        PieceSquareTables {
+            pawn: PhaseTables::from_heat_map(ChessPiece::BPawn),
+            knight: PhaseTables::from_heat_map(ChessPiece::BKnight),
+            bishop: PhaseTables::from_heat_map(ChessPiece::BBishop),
+            rook: PhaseTables::from_heat_map(ChessPiece::BRook),
+            queen: PhaseTables::from_heat_map(ChessPiece::BQueen),
+            king: PhaseTables::from_heat_map(ChessPiece::BKing),
+        }
+    }
+}
+
+// heat map values of a piece on `position` in the opening, middle game and endgame tables
+pub fn heat(
+    tables: &PieceSquareTables,
+    piece: ChessPiece,
+    color: PieceColor,
+    position: i32,
+) -> [i32; 3] {
+    let tables = match tables.for_piece(piece) {
+        Some(tables) => tables,
+        None => return [0; 3],
+    };
+    // the tables are for black, white reads them upside down
+    let row = if color == PieceColor::White {
+        7 - position / 8
+    } else {
+        position / 8
+    } as usize;
+    let col = (position % 8) as usize;
+    [
+        tables.opening[row][col],
+        tables.middle[row][col],
+        tables.endgame[row][col],
+    ]
+}
+
+// opening, middle game and endgame values blended between the two tables on either side of
+// the phase, so the score moves smoothly as material comes off instead of jumping between
+// tables; sums of heat values blend the same way
+pub fn blend_heat(values: [i32; 3], phase: i32) -> i32 {
+    let [opening, middle, endgame] = values;
+    let phase = phase.clamp(0, MAX_PHASE);
+    if phase > MIDDLE_PHASE {
+        let span = MAX_PHASE - MIDDLE_PHASE;
+        let weight = phase - MIDDLE_PHASE;
+        (opening * weight + middle * (span - weight)) / span
+    } else {
+        (middle * phase + endgame * (MIDDLE_PHASE - phase)) / MIDDLE_PHASE
+    }
+}
+
 pub fn piece_heat_map(piece: ChessPiece, color: PieceColor, game_type: &GameType) -> [[i32; 8]; 8] {
     let pawn_opening: [[i32; 8]; 8] = [
         [0, 0, 0, 0, 0, 0, 0, 0],
@@ -14,7 +152,7 @@ pub fn piece_heat_map(piece: ChessPiece, color: PieceColor, game_type: &GameType
         [20, 20, 20, 70, 70, 20, 20, 60],
         [0, 0, 0, 80, 80, 0, 0, 0],
         [-5, -5, -5, 50, 50, 30, -5, -5],
-        [-10, -10, -10, 10, 10, -20, 1 - 0, -10],
+        [-10, -10, -10, 10, 10, -20, -10, -10],
         [5, 5, 10, 20, 20, 10, 5, 5],
         [0, 0, 0, 0, 0, 0, 0, 0],
     ];
@@ -279,7 +417,7 @@ pub fn piece_heat_map(piece: ChessPiece, color: PieceColor, game_type: &GameType
                         } else if game_type == &GameType::Middle {
                             reversed[i][j] = king_middle[7 - i][j];
                         } else {
-                            reversed[i][j] = knight_endgame[7 - i][j];
+                            reversed[i][j] = king_endgame[7 - i][j];
                         }
                     }
                 }
@@ -294,7 +432,7 @@ pub fn piece_heat_map(piece: ChessPiece, color: PieceColor, game_type: &GameType
                         } else if game_type == &GameType::Middle {
                             reversed[i][j] = knight_middle[7 - i][j];
                         } else {
-                            reversed[i][j] = king_endgame[7 - i][j];
+                            reversed[i][j] = knight_endgame[7 - i][j];
                         }
                     }
                 }
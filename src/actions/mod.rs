@@ -1,5 +1,17 @@
+pub mod attacks;
+pub mod book;
 pub mod capture;
+pub mod endgame;
+pub mod evaluation;
+pub mod movegen;
 pub mod path;
+pub mod packed;
+pub mod params;
+pub mod pawns;
+pub mod pgn;
 pub mod play;
 pub mod player;
+pub mod position;
+pub mod square;
+pub mod transposition;
 pub mod heatmap;
@@ -0,0 +1,356 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::interface::chessboard::piece::{fen_to_board, ChessPiece, Color};
+
+use super::{
+    attacks::{is_attacked, piece_color},
+    packed::{PackedMove, Promotion},
+    play::{Game, MoveType},
+    position::{
+        castling_rights, Position, BLACK_KING_SIDE, BLACK_QUEEN_SIDE, WHITE_KING_SIDE,
+        WHITE_QUEEN_SIDE,
+    },
+    square::{File, Rank, Square, ALL_WAYS, DIAGONAL, KNIGHT_JUMPS, STRAIGHT},
+};
+
+// no legal position has more than 218 moves
+pub const MAX_MOVES: usize = 256;
+
+// fixed size buffer the generators write into, it lives on the stack of whoever asks for
+// moves and can be cleared and filled again without allocating
+#[derive(Debug, Clone)]
+pub struct MoveList {
+    moves: [PackedMove; MAX_MOVES],
+    len: usize,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        MoveList {
+            moves: [PackedMove::default(); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, mv: PackedMove) {
+        debug_assert!(self.len < MAX_MOVES, "move list is full");
+        if self.len < MAX_MOVES {
+            self.moves[self.len] = mv;
+            self.len += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [PackedMove];
+
+    fn deref(&self) -> &[PackedMove] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [PackedMove] {
+        &mut self.moves[..self.len]
+    }
+}
+
+// the pawn that can be taken en passant; the fen of this game keeps the square the pawn
+// moved to rather than the one it skipped
+pub fn en_passant_pawn(fen: &str) -> Option<Square> {
+    fen.split(' ').nth(3).and_then(Square::from_algebraic)
+}
+
+fn is_enemy(piece: ChessPiece, color: Color) -> bool {
+    piece_color(piece).is_some_and(|piece_color| piece_color != color)
+}
+
+fn push_pawn_move(list: &mut MoveList, from: Square, to: Square, color: Color) {
+    if to.rank() == Rank::promotion(color) {
+        // every piece the pawn can become is a move of its own
+        for promotion in Promotion::ALL {
+            list.push(PackedMove::new(
+                from,
+                to,
+                MoveType::Promotion,
+                Some(promotion),
+            ));
+        }
+    } else {
+        list.push(PackedMove::new(from, to, MoveType::Normal, None));
+    }
+}
+
+// squares a knight, king or slider on `from` reaches, handed to `visit` with whatever
+// stands there; sliders stop at the first piece
+pub fn piece_targets(
+    board: &[ChessPiece; 64],
+    piece: ChessPiece,
+    from: Square,
+    mut visit: impl FnMut(Square, ChessPiece),
+) {
+    let (steps, slides): (&[(i32, i32)], bool) = match piece {
+        ChessPiece::WKnight | ChessPiece::BKnight => (&KNIGHT_JUMPS, false),
+        ChessPiece::WBishop | ChessPiece::BBishop => (&DIAGONAL, true),
+        ChessPiece::WRook | ChessPiece::BRook => (&STRAIGHT, true),
+        ChessPiece::WQueen | ChessPiece::BQueen => (&ALL_WAYS, true),
+        ChessPiece::WKing | ChessPiece::BKing => (&ALL_WAYS, false),
+        _ => return,
+    };
+    for &(files, ranks) in steps {
+        let mut square = from;
+        while let Some(next) = square.offset(files, ranks) {
+            let target = board[next.index() as usize];
+            visit(next, target);
+            if !slides || target != ChessPiece::None {
+                break;
+            }
+            square = next;
+        }
+    }
+}
+
+// moves of `color` that take a piece, en passant and capturing promotions included
+pub fn generate_captures(
+    board: &[ChessPiece; 64],
+    color: Color,
+    en_passant: Option<Square>,
+    list: &mut MoveList,
+) {
+    let forward = if color == Color::White { 1 } else { -1 };
+    for (position, piece) in board.iter().enumerate() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        let from = match Square::new(position as i32) {
+            Some(from) => from,
+            None => continue,
+        };
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => {
+                for files in [-1, 1] {
+                    if let Some(to) = from.offset(files, forward) {
+                        if is_enemy(board[to.index() as usize], color) {
+                            push_pawn_move(list, from, to, color);
+                        }
+                    }
+                }
+                let beside = en_passant.filter(|pawn| {
+                    pawn.rank() == from.rank()
+                        && pawn.file_distance(from) == 1
+                        && is_enemy(board[pawn.index() as usize], color)
+                });
+                if let Some(to) = beside.and_then(|pawn| pawn.offset(0, forward)) {
+                    if board[to.index() as usize] == ChessPiece::None {
+                        list.push(PackedMove::new(from, to, MoveType::EnPassant, None));
+                    }
+                }
+            }
+            _ => piece_targets(board, *piece, from, |to, target| {
+                if is_enemy(target, color) {
+                    list.push(PackedMove::new(from, to, MoveType::Normal, None));
+                }
+            }),
+        }
+    }
+}
+
+// moves of `color` to empty squares: pushes, promotions by pushing and castling included
+pub fn generate_quiets(board: &[ChessPiece; 64], color: Color, castling: u8, list: &mut MoveList) {
+    let forward = if color == Color::White { 1 } else { -1 };
+    for (position, piece) in board.iter().enumerate() {
+        if piece_color(*piece) != Some(color) {
+            continue;
+        }
+        let from = match Square::new(position as i32) {
+            Some(from) => from,
+            None => continue,
+        };
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => {
+                let one = match from.offset(0, forward) {
+                    Some(one) if board[one.index() as usize] == ChessPiece::None => one,
+                    _ => continue,
+                };
+                push_pawn_move(list, from, one, color);
+                if from.rank() == Rank::pawn_start(color) {
+                    if let Some(two) = one.offset(0, forward) {
+                        if board[two.index() as usize] == ChessPiece::None {
+                            list.push(PackedMove::new(from, two, MoveType::Normal, None));
+                        }
+                    }
+                }
+            }
+            ChessPiece::WKing | ChessPiece::BKing => {
+                piece_targets(board, *piece, from, |to, target| {
+                    if target == ChessPiece::None {
+                        list.push(PackedMove::new(from, to, MoveType::Normal, None));
+                    }
+                });
+                generate_castling(board, color, from, castling, list);
+            }
+            _ => piece_targets(board, *piece, from, |to, target| {
+                if target == ChessPiece::None {
+                    list.push(PackedMove::new(from, to, MoveType::Normal, None));
+                }
+            }),
+        }
+    }
+}
+
+// the king can't castle out of or through check, landing in check is left to make_move
+fn generate_castling(
+    board: &[ChessPiece; 64],
+    color: Color,
+    king: Square,
+    castling: u8,
+    list: &mut MoveList,
+) {
+    let back = Rank::back(color);
+    if king != Square::at(File::E, back) {
+        return;
+    }
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let (king_side, queen_side) = if color == Color::White {
+        (WHITE_KING_SIDE, WHITE_QUEEN_SIDE)
+    } else {
+        (BLACK_KING_SIDE, BLACK_QUEEN_SIDE)
+    };
+    let empty = |files: &[File]| {
+        files
+            .iter()
+            .all(|file| board[Square::at(*file, back).index() as usize] == ChessPiece::None)
+    };
+    let safe = |file: File| !is_attacked(board, Square::at(file, back).index(), opponent);
+
+    if castling & king_side != 0 && empty(&[File::F, File::G]) && safe(File::E) && safe(File::F) {
+        list.push(PackedMove::new(
+            king,
+            Square::at(File::G, back),
+            MoveType::Castle,
+            None,
+        ));
+    }
+    if castling & queen_side != 0
+        && empty(&[File::D, File::C, File::B])
+        && safe(File::E)
+        && safe(File::D)
+    {
+        list.push(PackedMove::new(
+            king,
+            Square::at(File::C, back),
+            MoveType::Castle,
+            None,
+        ));
+    }
+}
+
+// every move of `color` by how the pieces move, make_move still turns down the ones that
+// leave the king in check
+pub fn generate_moves(
+    board: &[ChessPiece; 64],
+    color: Color,
+    en_passant: Option<Square>,
+    castling: u8,
+    list: &mut MoveList,
+) {
+    generate_captures(board, color, en_passant, list);
+    generate_quiets(board, color, castling, list);
+}
+
+impl Game {
+    pub fn generate_captures(&self, color: Color, list: &mut MoveList) {
+        let board = fen_to_board(&self.fen);
+        generate_captures(&board, color, en_passant_pawn(&self.fen), list);
+    }
+
+    pub fn generate_quiets(&self, color: Color, list: &mut MoveList) {
+        let board = fen_to_board(&self.fen);
+        let castling = castling_rights(self.fen.split(' ').nth(2).unwrap_or("-"));
+        generate_quiets(&board, color, castling, list);
+    }
+
+    pub fn generate_moves(&self, color: Color, list: &mut MoveList) {
+        self.generate_captures(color, list);
+        self.generate_quiets(color, list);
+    }
+}
+
+impl Position {
+    // moves of the side to move, see generate_captures and generate_quiets
+    pub fn generate_captures(&self, list: &mut MoveList) {
+        generate_captures(&self.board, self.turn, self.en_passant(), list);
+    }
+
+    pub fn generate_quiets(&self, list: &mut MoveList) {
+        generate_quiets(&self.board, self.turn, self.castling(), list);
+    }
+
+    pub fn generate_moves(&self, list: &mut MoveList) {
+        self.generate_captures(list);
+        self.generate_quiets(list);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staged_generation() {
+        // white pawns take on d5, f5 and en passant on f6, the rooks take theirs and both
+        // sides are still open for castling
+        let game = Game::new("r3k2r/8/8/3pPp2/4P3/8/8/R3K2R w KQkq f5 0 1", None);
+        let mut list = MoveList::new();
+        game.generate_captures(Color::White, &mut list);
+        let mut captures = list.iter().map(|mv| mv.to_string()).collect::<Vec<_>>();
+        captures.sort();
+        assert_eq!(captures, vec!["a1a8", "e4d5", "e4f5", "e5f6", "h1h8"]);
+        let en_passant = list.iter().find(|mv| mv.to_string() == "e5f6").unwrap();
+        assert_eq!(en_passant.move_type(), MoveType::EnPassant);
+        let captured = list.len();
+
+        list.clear();
+        game.generate_quiets(Color::White, &mut list);
+        let castles = list
+            .iter()
+            .filter(|mv| mv.move_type() == MoveType::Castle)
+            .count();
+        assert_eq!(castles, 2);
+        assert!(list
+            .iter()
+            .all(|mv| !mv.is_capture(&fen_to_board(&game.fen))));
+
+        // both lists together are what the old generator found, played or not
+        let mut all = MoveList::new();
+        game.generate_moves(Color::White, &mut all);
+        assert_eq!(all.len(), captured + list.len());
+
+        // the well known kiwipete position has 48 legal moves, both castles among them
+        let game = Game::new(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            None,
+        );
+        all.clear();
+        game.generate_moves(Color::White, &mut all);
+        let legal = all
+            .iter()
+            .filter(|mv| game.clone().make_move(&mv.to_move(&game)).0)
+            .count();
+        assert_eq!(legal, 48);
+    }
+}
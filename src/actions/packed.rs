@@ -0,0 +1,274 @@
+use std::fmt;
+
+use crate::interface::chessboard::piece::{fen_to_board, ChessPiece, Color};
+
+use super::{
+    path::PossibleMoves,
+    play::{Game, Move, MoveType},
+    square::{Rank, Square},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Promotion {
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+}
+
+impl Promotion {
+    // the order promotions are generated in, the likely best one first
+    pub const ALL: [Promotion; 4] = [
+        Promotion::Queen,
+        Promotion::Rook,
+        Promotion::Bishop,
+        Promotion::Knight,
+    ];
+
+    // the letters Move::promote uses, "q", "r", "b" and "n"
+    pub fn from_letter(letter: &str) -> Option<Promotion> {
+        match letter {
+            "q" => Some(Promotion::Queen),
+            "r" => Some(Promotion::Rook),
+            "b" => Some(Promotion::Bishop),
+            "n" => Some(Promotion::Knight),
+            _ => None,
+        }
+    }
+
+    pub fn letter(self) -> &'static str {
+        match self {
+            Promotion::Queen => "q",
+            Promotion::Rook => "r",
+            Promotion::Bishop => "b",
+            Promotion::Knight => "n",
+        }
+    }
+
+    pub fn piece(self, color: Color) -> ChessPiece {
+        match (self, color == Color::White) {
+            (Promotion::Queen, true) => ChessPiece::WQueen,
+            (Promotion::Queen, false) => ChessPiece::BQueen,
+            (Promotion::Rook, true) => ChessPiece::WRook,
+            (Promotion::Rook, false) => ChessPiece::BRook,
+            (Promotion::Bishop, true) => ChessPiece::WBishop,
+            (Promotion::Bishop, false) => ChessPiece::BBishop,
+            (Promotion::Knight, true) => ChessPiece::WKnight,
+            (Promotion::Knight, false) => ChessPiece::BKnight,
+        }
+    }
+}
+
+const NORMAL: u16 = 0;
+const CASTLE: u16 = 1;
+const EN_PASSANT: u16 = 2;
+const PROMOTION: u16 = 3;
+
+// a move in 16 bits for the move generator and the search: from square in bits 0-5, to
+// square in bits 6-11, the move type in bits 12-13 and the promotion piece in 14-15;
+// the rich Move with its strings is only built to play the move on the board
+//
+// all zero bits, a8 to a8, is never a move and stands for no move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PackedMove(u16);
+
+fn square(bits: u16) -> Square {
+    Square::new((bits & 63) as i32).unwrap_or(Square::A8)
+}
+
+impl PackedMove {
+    pub fn new(
+        from: Square,
+        to: Square,
+        move_type: MoveType,
+        promotion: Option<Promotion>,
+    ) -> PackedMove {
+        let kind = match move_type {
+            MoveType::Normal => NORMAL,
+            MoveType::Castle => CASTLE,
+            MoveType::EnPassant => EN_PASSANT,
+            MoveType::Promotion => PROMOTION,
+        };
+        let piece = if kind == PROMOTION {
+            promotion.unwrap_or(Promotion::Queen) as u16
+        } else {
+            0
+        };
+        PackedMove(from.index() as u16 | (to.index() as u16) << 6 | kind << 12 | piece << 14)
+    }
+
+    pub fn from_bits(bits: u16) -> PackedMove {
+        PackedMove(bits)
+    }
+
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    pub fn is_none(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn from(self) -> Square {
+        square(self.0)
+    }
+
+    pub fn to(self) -> Square {
+        square(self.0 >> 6)
+    }
+
+    pub fn move_type(self) -> MoveType {
+        match (self.0 >> 12) & 3 {
+            CASTLE => MoveType::Castle,
+            EN_PASSANT => MoveType::EnPassant,
+            PROMOTION => MoveType::Promotion,
+            _ => MoveType::Normal,
+        }
+    }
+
+    pub fn promotion(self) -> Option<Promotion> {
+        (self.move_type() == MoveType::Promotion).then_some(match self.0 >> 14 {
+            0 => Promotion::Knight,
+            1 => Promotion::Bishop,
+            2 => Promotion::Rook,
+            _ => Promotion::Queen,
+        })
+    }
+
+    // the piece taken, for en passant the pawn beside the moving one
+    pub fn captured_piece(self, board: &[ChessPiece; 64]) -> ChessPiece {
+        let to = self.to();
+        if self.move_type() == MoveType::EnPassant {
+            let beside = Square::at(to.file(), self.from().rank());
+            return board[beside.index() as usize];
+        }
+        board[to.index() as usize]
+    }
+
+    pub fn is_capture(self, board: &[ChessPiece; 64]) -> bool {
+        self.captured_piece(board) != ChessPiece::None
+    }
+
+    // a move given only by its squares, as the board and older callers hand them over, with
+    // the move type worked out from the position: a pawn reaching the last rank promotes,
+    // to a queen unless it says otherwise, a pawn going diagonally to an empty square takes
+    // en passant and a king going two files castles
+    pub fn from_possible(board: &[ChessPiece; 64], mv: &PossibleMoves) -> PackedMove {
+        let (from, to) = match (Square::new(mv.from), Square::new(mv.to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return PackedMove::default(),
+        };
+        let is_pawn = mv.piece == ChessPiece::WPawn || mv.piece == ChessPiece::BPawn;
+        let is_king = mv.piece == ChessPiece::WKing || mv.piece == ChessPiece::BKing;
+        let promotion = mv.promote_to.as_deref().and_then(Promotion::from_letter);
+        let move_type = if is_pawn && to.rank() == Rank::promotion(mv.piece.color()) {
+            MoveType::Promotion
+        } else if is_pawn
+            && from.file() != to.file()
+            && board[to.index() as usize] == ChessPiece::None
+        {
+            MoveType::EnPassant
+        } else if is_king && from.file_distance(to) == 2 {
+            MoveType::Castle
+        } else {
+            MoveType::Normal
+        };
+        PackedMove::new(from, to, move_type, promotion)
+    }
+
+    // the packed form of a move as make_move takes it, promotion piece included
+    pub fn from_move(mv: &Move) -> PackedMove {
+        match (Square::new(mv.from), Square::new(mv.to)) {
+            (Some(from), Some(to)) => PackedMove::new(
+                from,
+                to,
+                mv.move_type.clone(),
+                Promotion::from_letter(&mv.promote),
+            ),
+            _ => PackedMove::default(),
+        }
+    }
+
+    pub fn to_possible(self, board: &[ChessPiece; 64]) -> PossibleMoves {
+        let promotion = self.promotion();
+        PossibleMoves {
+            from: self.from().index(),
+            to: self.to().index(),
+            piece: board[self.from().index() as usize],
+            promote: promotion.is_some(),
+            promote_to: promotion.map(|piece| piece.letter().to_string()),
+            move_type: self.move_type(),
+            captured_piece: self.captured_piece(board),
+        }
+    }
+
+    // the full move make_move and the board play
+    pub fn to_move(self, game: &Game) -> Move {
+        let board = fen_to_board(&game.fen);
+        let castling_rights = game.fen.split(" ").nth(2).unwrap_or("-");
+        Move {
+            from: self.from().index(),
+            to: self.to().index(),
+            piece: board[self.from().index() as usize],
+            promote: self
+                .promotion()
+                .map_or(String::new(), |piece| piece.letter().to_string()),
+            move_type: self.move_type(),
+            captured_piece: self.captured_piece(&board),
+            castling_rights: castling_rights.to_string(),
+        }
+    }
+}
+
+// long algebraic notation, such as e2e4 or e7e8q
+impl fmt::Display for PackedMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from(), self.to())?;
+        match self.promotion() {
+            Some(piece) => write!(f, "{}", piece.letter()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packed_moves() {
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+
+        let e7 = Square::from_algebraic("e7").unwrap();
+        let d8 = Square::D8;
+        let mv = PackedMove::new(e7, d8, MoveType::Promotion, Some(Promotion::Knight));
+        assert_eq!((mv.from(), mv.to()), (e7, d8));
+        assert_eq!(mv.move_type(), MoveType::Promotion);
+        assert_eq!(mv.promotion(), Some(Promotion::Knight));
+        assert_eq!(PackedMove::from_bits(mv.bits()), mv);
+        assert_eq!(mv.to_string(), "e7d8n");
+        assert!(PackedMove::default().is_none() && !mv.is_none());
+
+        // the pawn on e5 takes d6 en passant, the black pawn on d5 goes
+        let game = Game::new("4k3/8/8/3pP3/8/8/8/4K3 w - d5 0 1", None);
+        let board = fen_to_board(&game.fen);
+        let mv = PackedMove::from_possible(
+            &board,
+            &PossibleMoves {
+                from: 28,
+                to: 19,
+                piece: ChessPiece::WPawn,
+                promote: false,
+                promote_to: None,
+                move_type: MoveType::Normal,
+                captured_piece: ChessPiece::None,
+            },
+        );
+        assert_eq!(mv.move_type(), MoveType::EnPassant);
+        assert_eq!(mv.captured_piece(&board), ChessPiece::BPawn);
+        let move_ = mv.to_move(&game);
+        assert_eq!(move_.captured_piece, ChessPiece::BPawn);
+        assert!(game.clone().make_move(&move_).0);
+        assert_eq!(PackedMove::from_move(&move_), mv);
+    }
+}
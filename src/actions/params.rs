@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    evaluation::{KingSafetyParams, MaterialParams, MobilityParams},
+    heatmap::PieceSquareTables,
+    pawns::PawnParams,
+};
+
+// file next to the game that is picked up at startup when it exists
+pub const EVAL_PARAMS_FILE: &str = "eval_params.json";
+
+// every weight the evaluation uses, missing fields in a json file keep their default
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    pub material: MaterialParams,
+    pub piece_square: PieceSquareTables,
+    pub pawns: PawnParams,
+    pub king_safety: KingSafetyParams,
+    pub mobility: MobilityParams,
+}
+
+impl EvalParams {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| format!("invalid evaluation parameters: {}", err))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Self::from_json(&json)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_json()).map_err(|err| format!("{}: {}", path, err))
+    }
+}
+
+static EVAL_PARAMS: LazyLock<RwLock<Arc<EvalParams>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(EvalParams::default())));
+
+// parameters the engine currently evaluates with
+pub fn eval_params() -> Arc<EvalParams> {
+    match EVAL_PARAMS.read() {
+        Ok(params) => params.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+// swap the parameters at runtime, searches already running keep the ones they started with
+pub fn set_eval_params(params: EvalParams) {
+    let params = Arc::new(params);
+    match EVAL_PARAMS.write() {
+        Ok(mut current) => *current = params,
+        Err(poisoned) => *poisoned.into_inner() = params,
+    }
+}
+
+pub fn load_eval_params(path: &str) -> Result<(), String> {
+    set_eval_params(EvalParams::load(path)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_params_json() {
+        let params = EvalParams::default();
+        assert_eq!(EvalParams::from_json(&params.to_json()), Ok(params.clone()));
+
+        // fields left out keep their defaults
+        let partial = EvalParams::from_json(r#"{"material": {"knight": 300}}"#).unwrap();
+        assert_eq!(partial.material.knight, 300);
+        assert_eq!(partial.material.bishop, params.material.bishop);
+        assert_eq!(partial.piece_square, params.piece_square);
+        assert!(EvalParams::from_json(r#"{"material": {"knight": "a"}}"#).is_err());
+    }
+}
@@ -1,11 +1,12 @@
-use crate::{
-    actions::play::name_moves,
-    interface::chessboard::piece::{fen_to_board, ChessPiece, Color},
-};
+use crate::interface::chessboard::piece::{fen_to_board, ChessPiece, Color};
 
 use super::{
-    capture::{get_pieces_by_color, in_check},
-    play::{Game, GameState, Move, MoveType},
+    capture::in_check,
+    movegen::MoveList,
+    packed::PackedMove,
+    play::{Game, MoveType},
+    position::Position,
+    square::{Rank, Square},
 };
 
 pub fn bishop_possible_squares(
@@ -16,26 +17,22 @@ pub fn bishop_possible_squares(
     let color = piece.color();
     let mut capture_squares: Vec<i32> = vec![];
     let mut non_capture_squares: Vec<i32> = vec![];
+    let start = match Square::new(position) {
+        Some(start) => start,
+        None => return (capture_squares, non_capture_squares),
+    };
 
     // Define the possible directions for a bishop (diagonal)
     let directions: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
 
     // Loop through each direction
-    for &(dx, dy) in &directions {
-        let mut x = position / 8;
-        let mut y = position % 8;
+    for &(files, ranks) in &directions {
+        let mut square = start;
 
         // Move along the diagonal until we reach the edge of the board or an occupied square
-        loop {
-            x += dx;
-            y += dy;
-
-            // Check if the new position is out of bounds
-            if x < 0 || x >= 8 || y < 0 || y >= 8 {
-                break;
-            }
-
-            let new_position = x * 8 + y;
+        while let Some(next) = square.offset(files, ranks) {
+            square = next;
+            let new_position = square.index();
             let target_piece = board_pieces[new_position as usize];
 
             // If the square is empty, it's a valid move square (non-capture)
@@ -63,26 +60,22 @@ pub fn rook_possible_squares(
     let color = piece.color();
     let mut capture_squares: Vec<i32> = vec![];
     let mut non_capture_squares: Vec<i32> = vec![];
+    let start = match Square::new(position) {
+        Some(start) => start,
+        None => return (capture_squares, non_capture_squares),
+    };
 
     // Define the possible directions for a rook (vertical and horizontal)
     let directions: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
 
     // Loop through each direction
-    for &(dx, dy) in &directions {
-        let mut x = position / 8;
-        let mut y = position % 8;
+    for &(files, ranks) in &directions {
+        let mut square = start;
 
         // Move along the direction until we reach the edge of the board or an occupied square
-        loop {
-            x += dx;
-            y += dy;
-
-            // Check if the new position is out of bounds
-            if x < 0 || x >= 8 || y < 0 || y >= 8 {
-                break;
-            }
-
-            let new_position = x * 8 + y;
+        while let Some(next) = square.offset(files, ranks) {
+            square = next;
+            let new_position = square.index();
             let target_piece = board_pieces[new_position as usize];
 
             // If the square is empty, it's a valid move square (non-capture)
@@ -143,15 +136,11 @@ pub fn knight_possible_squares(
         (-2, -1),
     ];
 
-    for &(dx, dy) in &moves {
-        let x = position / 8;
-        let y = position % 8;
-        let new_x = x + dx;
-        let new_y = y + dy;
-
-        // Check if the new position is within the bounds of the board
-        if new_x >= 0 && new_x < 8 && new_y >= 0 && new_y < 8 {
-            let new_position = new_x * 8 + new_y;
+    let start = Square::new(position);
+    for &(files, ranks) in &moves {
+        // Only squares within the bounds of the board
+        if let Some(square) = start.and_then(|start| start.offset(files, ranks)) {
+            let new_position = square.index();
             let target_piece = board_pieces[new_position as usize];
 
             if target_piece == ChessPiece::None {
@@ -193,30 +182,25 @@ pub fn pawn_forward_move(
 ) -> Vec<i32> {
     let mut squares: Vec<i32> = vec![];
     let color = piece.color();
-    let mut direction = 1;
-    if color == Color::White {
-        direction = -1;
-    }
-
-    let x = position / 8;
-    let y = position % 8;
+    let direction = if color == Color::White { 1 } else { -1 };
+    let start = match Square::new(position) {
+        Some(start) => start,
+        None => return squares,
+    };
 
     // Check one square forward
-    let target_square = (x + direction) * 8 + y;
-    if target_square >= 0 && target_square < 64 && board[target_square as usize] == ChessPiece::None
-    {
-        squares.push(target_square);
-    }
+    let target_square = match start.offset(0, direction) {
+        Some(square) if board[square.index() as usize] == ChessPiece::None => square,
+        _ => return squares,
+    };
+    squares.push(target_square.index());
 
     // Check two squares forward if it's the pawn's initial move
-    if (color == Color::White && x == 6) || (color == Color::Black && x == 1) {
-        let target_square_2 = (x + 2 * direction) * 8 + y;
-        if target_square_2 >= 0
-            && target_square_2 < 64
-            && board[target_square_2 as usize] == ChessPiece::None
-            && board[target_square as usize] == ChessPiece::None
-        {
-            squares.push(target_square_2);
+    if start.rank() == Rank::pawn_start(color) {
+        if let Some(target_square_2) = target_square.offset(0, direction) {
+            if board[target_square_2.index() as usize] == ChessPiece::None {
+                squares.push(target_square_2.index());
+            }
         }
     }
     squares
@@ -229,15 +213,13 @@ pub fn pawn_capture_squares(
 ) -> Vec<i32> {
     let mut squares: Vec<i32> = Vec::new();
     let color = piece.color();
-    let direction = if color == Color::White { -1 } else { 1 };
-
-    let left_offset = direction * 8 - 1;
-    let right_offset = direction * 8 + 1;
+    let direction = if color == Color::White { 1 } else { -1 };
+    let start = Square::new(position);
 
-    // Calculate potential capture squares
-    for &offset in &[left_offset, right_offset] {
-        let target_square = position + offset;
-        if target_square >= 0 && target_square < 64 {
+    // Calculate potential capture squares, a pawn on the edge only has one
+    for files in [-1, 1] {
+        if let Some(target_square) = start.and_then(|start| start.offset(files, direction)) {
+            let target_square = target_square.index();
             let target_piece = board[target_square as usize];
             if target_piece != ChessPiece::None && target_piece.color() != color {
                 squares.push(target_square);
@@ -262,29 +244,23 @@ pub fn enpassant_moves(from: i32, piece: ChessPiece, fen: &str) -> Vec<i32> {
         return vec![];
     }
 
-    let mut enpassant_sqr: Option<i32> = None;
-    if enpassant_part != "-" {
-        enpassant_sqr = Some(
-            (enpassant_part.chars().nth(0).unwrap() as i32 - 97) + // a-h
-        (8 * (7-(enpassant_part.chars().nth(1).unwrap() as i32 - 49))),
-        );
-    }
-
-    if enpassant_sqr == None {
-        return vec![];
-    }
-
-    let enpassant_sqr = enpassant_sqr.unwrap();
+    // the fen keeps the square of the pawn that just moved two squares
+    let (enpassant_sqr, from_sqr) =
+        match (Square::from_algebraic(enpassant_part), Square::new(from)) {
+            (Some(enpassant_sqr), Some(from_sqr)) => (enpassant_sqr, from_sqr),
+            _ => return vec![],
+        };
 
-    if from != enpassant_sqr - 1 && from != enpassant_sqr + 1 {
+    if from_sqr.rank() != enpassant_sqr.rank() || from_sqr.file_distance(enpassant_sqr) != 1 {
         return vec![];
     }
 
-    let move_sqr = if color == Color::White {
-        enpassant_sqr - 8
-    } else {
-        enpassant_sqr + 8
+    let direction = if color == Color::White { 1 } else { -1 };
+    let move_sqr = match enpassant_sqr.offset(0, direction) {
+        Some(square) => square.index(),
+        None => return vec![],
     };
+    let enpassant_sqr = enpassant_sqr.index();
 
     let emp_piece = board[enpassant_sqr as usize];
     if emp_piece == ChessPiece::None || emp_piece.color() == color {
@@ -310,16 +286,11 @@ pub fn king_adjuscent_squares(position: i32) -> Vec<i32> {
         (0, -1),
     ];
 
-    for &(dx, dy) in &moves {
-        let x = position / 8;
-        let y = position % 8;
-        let new_x = x + dx;
-        let new_y = y + dy;
-
-        // Check if the new position is within the bounds of the board
-        if new_x >= 0 && new_x < 8 && new_y >= 0 && new_y < 8 {
-            let new_position = new_x * 8 + new_y;
-            squares.push(new_position);
+    let start = Square::new(position);
+    for &(files, ranks) in &moves {
+        // Only squares within the bounds of the board
+        if let Some(square) = start.and_then(|start| start.offset(files, ranks)) {
+            squares.push(square.index());
         }
     }
     squares
@@ -375,14 +346,10 @@ pub fn king_normal_squares(
         (0, -1),
     ];
 
-    for &(dx, dy) in &moves {
-        let x = position / 8;
-        let y = position % 8;
-        let new_x = x + dx;
-        let new_y = y + dy;
-
-        if new_x >= 0 && new_x < 8 && new_y >= 0 && new_y < 8 {
-            let new_position = new_x * 8 + new_y;
+    let start = Square::new(position);
+    for &(files, ranks) in &moves {
+        if let Some(square) = start.and_then(|start| start.offset(files, ranks)) {
+            let new_position = square.index();
 
             // opp king should not be able to attack the king's new position
             if opp_king_attacking_squares.contains(&new_position) {
@@ -415,17 +382,19 @@ pub fn king_castling(
     let color = piece.color();
     if color == Color::Black {
         // Check if the balck king is in its initial position
-        if position == 4 {
+        if position == Square::E8.index() {
             // Check if the balck king-side rook is in its initial position
             if castle_rules.contains('k') {
                 // Check if the squares between the king and the rook are empty
-                if board_pieces[5] == ChessPiece::None && board_pieces[6] == ChessPiece::None {
+                if board_pieces[Square::F8.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::G8.index() as usize] == ChessPiece::None
+                {
                     // Check if the king is not in check
                     let mut board = board_pieces.clone();
                     let is_check = in_check(&mut board, color);
                     if !is_check {
                         // Check if the king is not passing through a square that is attacked by an opponent's piece
-                        let skipping_square = 5;
+                        let skipping_square = Square::F8.index();
                         // see if king being at skipping_square is in check
                         let mut updated_board = board_pieces.clone();
                         updated_board[skipping_square as usize] = piece;
@@ -433,7 +402,7 @@ pub fn king_castling(
                         updated_board[position as usize] = ChessPiece::None;
                         let is_check = in_check(&mut updated_board, color);
                         if !is_check {
-                            squares.push(6);
+                            squares.push(Square::G8.index());
                         }
                     }
                 }
@@ -441,23 +410,23 @@ pub fn king_castling(
             // Check if the black queen-side rook is in its initial position
             if castle_rules.contains('q') {
                 // Check if the squares between the king and the rook are empty
-                if board_pieces[3] == ChessPiece::None
-                    && board_pieces[2] == ChessPiece::None
-                    && board_pieces[1] == ChessPiece::None
+                if board_pieces[Square::D8.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::C8.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::B8.index() as usize] == ChessPiece::None
                 {
                     // Check if the king is not in check
                     let mut board = board_pieces.clone();
                     let is_check = in_check(&mut board, color);
                     if !is_check {
                         // Check if the king is not passing through a square that is attacked by an opponent's piece
-                        let skipping_square = 3;
+                        let skipping_square = Square::D8.index();
                         // see if king being at skipping_square is in check
                         let mut updated_board = board_pieces.clone();
                         updated_board[skipping_square as usize] = piece;
                         updated_board[position as usize] = ChessPiece::None;
                         let is_check = in_check(&mut updated_board, color);
                         if !is_check {
-                            squares.push(2);
+                            squares.push(Square::C8.index());
                         }
                     }
                 }
@@ -465,25 +434,27 @@ pub fn king_castling(
         }
     } else {
         // Check if the white king is in its initial position
-        if position == 60 {
+        if position == Square::E1.index() {
             // Check if the white king-side rook is in its initial position
             if castle_rules.contains('K') {
                 // Check if the squares between the king and the rook are empty
-                if board_pieces[61] == ChessPiece::None && board_pieces[62] == ChessPiece::None {
+                if board_pieces[Square::F1.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::G1.index() as usize] == ChessPiece::None
+                {
                     // Check if the king is not in check
                     let mut board = board_pieces.clone();
                     let is_check = in_check(&mut board, color);
                     if !is_check {
                         // Check if the king is not passing through a square that is attacked by an opponent's piece
 
-                        let skipping_square = 61;
+                        let skipping_square = Square::F1.index();
                         // see if king being at skipping_square is in check
                         let mut updated_board = board_pieces.clone();
                         updated_board[skipping_square as usize] = piece;
                         updated_board[position as usize] = ChessPiece::None;
                         let is_check = in_check(&mut updated_board, color);
                         if !is_check {
-                            squares.push(62);
+                            squares.push(Square::G1.index());
                         }
                     }
                 }
@@ -491,23 +462,23 @@ pub fn king_castling(
             // Check if the white queen-side rook is in its initial position
             if castle_rules.contains('Q') {
                 // Check if the squares between the king and the rook are empty
-                if board_pieces[59] == ChessPiece::None
-                    && board_pieces[58] == ChessPiece::None
-                    && board_pieces[57] == ChessPiece::None
+                if board_pieces[Square::D1.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::C1.index() as usize] == ChessPiece::None
+                    && board_pieces[Square::B1.index() as usize] == ChessPiece::None
                 {
                     // Check if the king is not in check
                     let mut board = board_pieces.clone();
                     let is_check = in_check(&mut board, color);
                     if !is_check {
                         // Check if the king is not passing through a square that is attacked by an opponent's piece
-                        let skipping_square = 59;
+                        let skipping_square = Square::D1.index();
                         // see if king being at skipping_square is in check
                         let mut updated_board = board_pieces.clone();
                         updated_board[skipping_square as usize] = piece;
                         updated_board[position as usize] = ChessPiece::None;
                         let is_check = in_check(&mut updated_board, color);
                         if !is_check {
-                            squares.push(58);
+                            squares.push(Square::C1.index());
                         }
                     }
                 }
@@ -586,254 +557,36 @@ pub struct PossibleMoves {
 }
 
 impl Game {
+    // moves of `color` by how the pieces move, packed; make_move still turns down the ones
+    // that leave the king in check
+    pub fn packed_moves(&self, color: Color) -> Vec<PackedMove> {
+        let mut moves = MoveList::new();
+        self.generate_moves(color, &mut moves);
+        moves.to_vec()
+    }
+
     pub fn possible_moves(&self, color: Color) -> Vec<PossibleMoves> {
         let board = fen_to_board(&self.fen);
-        // return from and to squares
-        let color_pieces = get_pieces_by_color(&board, color);
-        let poss_moves: Vec<PossibleMoves> = color_pieces
+        self.packed_moves(color)
             .into_iter()
-            .flat_map(|(piece, position)| {
-                let mut poss_moves: Vec<PossibleMoves> = vec![];
-                let castle_rules = self.fen.split(" ").collect::<Vec<&str>>()[2];
-                let pawn_normal_moves = pawn_forward_move(&board, piece, position, &self.fen);
-                let pawn_capture_moves = pawn_capture_squares(&board, piece, position);
-                let pawn_enpassant_moves = enpassant_moves(position, piece, &self.fen);
-
-                let mut pawn_moves: Vec<i32> = vec![];
-                pawn_moves.extend(pawn_normal_moves);
-                pawn_moves.extend(pawn_capture_moves);
-                pawn_moves.extend(pawn_enpassant_moves);
-
-                poss_moves.extend(pawn_moves.into_iter().map(|to| PossibleMoves {
-                    from: position,
-                    to,
-                    piece,
-                    promote: false,
-                    promote_to: None,
-                    move_type: MoveType::Normal,
-                    captured_piece: board[to as usize].into(),
-                }));
-
-                if piece == ChessPiece::WKnight || piece == ChessPiece::BKnight {
-                    let (knight_capture_moves, knight_non_capture_moves) =
-                        knight_possible_squares(&board, piece, position);
-                    poss_moves.extend(
-                        knight_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: board[to as usize].into(),
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-
-                    poss_moves.extend(
-                        knight_non_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: ChessPiece::None,
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-                }
-
-                if piece == ChessPiece::WBishop || piece == ChessPiece::BBishop {
-                    let (bishop_capture_moves, bishop_non_capture_moves) =
-                        bishop_possible_squares(&board, piece, position);
-
-                    poss_moves.extend(
-                        bishop_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: board[to as usize].into(),
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-
-                    poss_moves.extend(
-                        bishop_non_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: ChessPiece::None,
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-                }
-
-                if piece == ChessPiece::WRook || piece == ChessPiece::BRook {
-                    let (rook_capture_moves, rook_non_capture_moves) =
-                        rook_possible_squares(&board, piece, position);
-
-                    poss_moves.extend(
-                        rook_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: board[to as usize].into(),
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-
-                    poss_moves.extend(
-                        rook_non_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: ChessPiece::None,
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-                }
-
-                if piece == ChessPiece::WQueen || piece == ChessPiece::BQueen {
-                    let (queen_capture_moves, queen_non_capture_moves) =
-                        queen_attacking_squares(&board, piece, position);
-
-                    poss_moves.extend(
-                        queen_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: board[to as usize].into(),
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-
-                    poss_moves.extend(
-                        queen_non_capture_moves
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: ChessPiece::None,
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-                }
-
-                if piece == ChessPiece::WKing || piece == ChessPiece::BKing {
-                    poss_moves.extend(
-                        king_normal_squares(&board, piece, position)
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Normal,
-                                captured_piece: board[to as usize].into(),
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-
-                    poss_moves.extend(
-                        king_castling(&board, piece, position, castle_rules)
-                            .into_iter()
-                            .map(|to| PossibleMoves {
-                                from: position,
-                                to,
-                                piece,
-                                promote: false,
-                                promote_to: None,
-                                move_type: MoveType::Castle,
-                                captured_piece: ChessPiece::None,
-                            })
-                            .collect::<Vec<PossibleMoves>>()
-                            .into_iter(),
-                    );
-                }
-
-                poss_moves
-            })
-            .collect::<Vec<PossibleMoves>>();
-
-        poss_moves
+            .map(|mv| mv.to_possible(&board))
+            .collect()
     }
 }
 
-fn count_possible_moves(depth: i32, game: &mut Game) -> i32 {
+fn count_possible_moves(depth: i32, position: &mut Position) -> i32 {
     if depth == 0 {
         return 1;
     }
 
-    let color_part = game.fen.split(" ").collect::<Vec<&str>>()[1];
-    let color = if color_part == "w" {
-        Color::White
-    } else {
-        Color::Black
-    };
-    let castling_part = game.fen.split(" ").collect::<Vec<&str>>()[2];
-    let moves: Vec<PossibleMoves> = game.possible_moves(color);
+    let mut moves = MoveList::new();
+    position.generate_moves(&mut moves);
     let mut num_posions = 0;
-    for mv in moves {
-        let move_ = Move {
-            from: mv.from,
-            to: mv.to,
-            piece: mv.piece,
-            promote: "".into(),
-            move_type: mv.move_type,
-            captured_piece: mv.captured_piece,
-            castling_rights: castling_part.into(),
-        };
-        let game = &mut game.clone();
-
-        game.make_move(&move_);
-        let count = count_possible_moves(depth - 1, game);
-        game.unmake_move(&move_);
-        let fen = game.fen.clone();
-        num_posions += count;
+    for mv in moves.iter().copied() {
+        if let Some(undo) = position.make_move(mv) {
+            num_posions += count_possible_moves(depth - 1, position);
+            position.unmake_move(mv, undo);
+        }
     }
     num_posions
 }
@@ -841,17 +594,37 @@ fn count_possible_moves(depth: i32, game: &mut Game) -> i32 {
 #[cfg(test)]
 #[test]
 fn test_possible_moves() {
+    // the standard perft counts, from the start position and from kiwipete, which has
+    // castling, en passant and promotions within a few moves
     let fen: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
-    let mut game = Game::new(fen.into(), None);
-    for i in 0..=3 {
-        let start_time = std::time::Instant::now();
-        let num_pos = count_possible_moves(i, &mut game);
-        let end_time = std::time::Instant::now();
-        println!(
-            "depth: {} position: {} time: {:?}",
-            i,
-            num_pos,
-            end_time - start_time
-        );
+    let mut position = Position::from_fen(fen);
+    for (depth, count) in [1, 20, 400, 8902].into_iter().enumerate() {
+        assert_eq!(count_possible_moves(depth as i32, &mut position), count);
     }
+
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let mut position = Position::from_fen(fen);
+    assert_eq!(count_possible_moves(2, &mut position), 2039);
+    assert_eq!(count_possible_moves(3, &mut position), 97862);
+}
+
+#[cfg(test)]
+#[test]
+fn test_promotion_moves() {
+    // the pawn on a7 can push or take on b8, the rook on the seventh rank is no pawn
+    let game = Game::new("1n5k/P6R/8/8/8/8/8/K7 w - - 0 1", None);
+    let promotions = game
+        .possible_moves(Color::White)
+        .into_iter()
+        .filter(|mv| mv.promote)
+        .collect::<Vec<PossibleMoves>>();
+    assert_eq!(promotions.len(), 8);
+    assert!(promotions
+        .iter()
+        .all(|mv| mv.piece == ChessPiece::WPawn && mv.move_type == MoveType::Promotion));
+    let knight_capture = promotions
+        .iter()
+        .find(|mv| mv.to == 1 && mv.promote_to.as_deref() == Some("n"))
+        .unwrap();
+    assert_eq!(knight_capture.captured_piece, ChessPiece::BKnight);
 }
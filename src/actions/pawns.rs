@@ -0,0 +1,369 @@
+use std::cell::RefCell;
+
+use serde::{Deserialize, Serialize};
+
+use crate::interface::chessboard::piece::{ChessPiece, Color};
+
+use super::{
+    attacks::piece_color,
+    heatmap::MAX_PHASE,
+    params::EvalParams,
+    square::{File, Rank, Square},
+};
+
+// (middle game, end game) centipawn weights of the pawn structure terms
+pub const DOUBLED_PAWN: (i32, i32) = (-10, -20);
+pub const ISOLATED_PAWN: (i32, i32) = (-10, -15);
+pub const BACKWARD_PAWN: (i32, i32) = (-8, -12);
+pub const PAWN_ISLAND: (i32, i32) = (-5, -10);
+// by rank counted from the pawn's own side, index 1 is the starting rank
+pub const CONNECTED_PAWN: [(i32, i32); 8] = [
+    (0, 0),
+    (3, 0),
+    (5, 2),
+    (8, 5),
+    (15, 10),
+    (25, 20),
+    (40, 30),
+    (0, 0),
+];
+pub const PASSED_PAWN: [(i32, i32); 8] = [
+    (0, 0),
+    (5, 10),
+    (10, 20),
+    (15, 35),
+    (25, 60),
+    (40, 100),
+    (60, 150),
+    (0, 0),
+];
+// a passed pawn with something standing in front of it only keeps this share (percent)
+pub const BLOCKED_PASSED_PAWN_PERCENT: i32 = 50;
+
+const PAWN_TABLE_BITS: u32 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PawnParams {
+    pub doubled: (i32, i32),
+    pub isolated: (i32, i32),
+    pub backward: (i32, i32),
+    pub island: (i32, i32),
+    pub connected: [(i32, i32); 8],
+    pub passed: [(i32, i32); 8],
+    pub blocked_passed_percent: i32,
+}
+
+impl Default for PawnParams {
+    fn default() -> Self {
+        PawnParams {
+            doubled: DOUBLED_PAWN,
+            isolated: ISOLATED_PAWN,
+            backward: BACKWARD_PAWN,
+            island: PAWN_ISLAND,
+            connected: CONNECTED_PAWN,
+            passed: PASSED_PAWN,
+            blocked_passed_percent: BLOCKED_PASSED_PAWN_PERCENT,
+        }
+    }
+}
+
+// pawn-only part of the evaluation, (middle game, end game) for each side
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PawnEntry {
+    pub white_pawns: u64,
+    pub black_pawns: u64,
+    pub white: (i32, i32),
+    pub black: (i32, i32),
+    // passed pawns are scored outside the entry since blockers are not pawns only
+    pub passed_pawns: u64,
+}
+
+// cache of pawn structure scores, indexed by the pawn placement of both sides
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+    // weights the entries were scored with, the table starts over when they change
+    params: PawnParams,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl PawnHashTable {
+    pub fn new() -> Self {
+        PawnHashTable {
+            entries: vec![None; 1 << PAWN_TABLE_BITS],
+            params: PawnParams::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn probe(&mut self, params: &PawnParams, board: &[ChessPiece; 64]) -> PawnEntry {
+        if self.params != *params {
+            self.clear();
+            self.params = *params;
+        }
+        let white_pawns = pawn_bitboard(board, ChessPiece::WPawn);
+        let black_pawns = pawn_bitboard(board, ChessPiece::BPawn);
+        let index = pawn_key(white_pawns, black_pawns);
+
+        if let Some(entry) = self.entries[index] {
+            if entry.white_pawns == white_pawns && entry.black_pawns == black_pawns {
+                self.hits += 1;
+                return entry;
+            }
+        }
+        self.misses += 1;
+        let entry = evaluate_pawns(params, white_pawns, black_pawns);
+        self.entries[index] = Some(entry);
+        entry
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.hits = 0;
+        self.misses = 0;
+    }
+}
+
+impl Default for PawnHashTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<PawnHashTable> = RefCell::new(PawnHashTable::new());
+}
+
+fn pawn_key(white_pawns: u64, black_pawns: u64) -> usize {
+    let mixed = (white_pawns ^ black_pawns.rotate_left(29)).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    (mixed >> (64 - PAWN_TABLE_BITS)) as usize
+}
+
+pub fn pawn_bitboard(board: &[ChessPiece; 64], pawn: ChessPiece) -> u64 {
+    let mut bitboard = 0;
+    for (i, piece) in board.iter().enumerate() {
+        if *piece == pawn {
+            bitboard |= 1 << i;
+        }
+    }
+    bitboard
+}
+
+fn file_mask(file: File) -> u64 {
+    0x0101_0101_0101_0101 << file.index()
+}
+
+fn rank_mask(rank: Rank) -> u64 {
+    0xFF << Square::at(File::A, rank).index()
+}
+
+fn adjacent_files_mask(file: File) -> u64 {
+    File::new(file.index() - 1).map_or(0, file_mask)
+        | File::new(file.index() + 1).map_or(0, file_mask)
+}
+
+// ranks strictly in front of `rank` as seen by `color`
+fn ranks_ahead_mask(rank: Rank, color: Color) -> u64 {
+    let mut mask = 0;
+    for r in Rank::ALL {
+        let ahead = if color == Color::White {
+            r > rank
+        } else {
+            r < rank
+        };
+        if ahead {
+            mask |= rank_mask(r);
+        }
+    }
+    mask
+}
+
+fn relative_rank(square: Square, color: Color) -> usize {
+    let rank = square.rank().index();
+    if color == Color::White {
+        rank as usize
+    } else {
+        (7 - rank) as usize
+    }
+}
+
+fn add(score: &mut (i32, i32), term: (i32, i32)) {
+    score.0 += term.0;
+    score.1 += term.1;
+}
+
+// pawn structure of one side in (middle game, end game) centipawns plus its passed pawns
+fn evaluate_side(params: &PawnParams, own: u64, enemy: u64, color: Color) -> ((i32, i32), u64) {
+    let mut score = (0, 0);
+    let mut passed = 0;
+    let forward = if color == Color::White { 1 } else { -1 };
+
+    for square in (0..64).filter_map(Square::new) {
+        if own & (1 << square.index()) == 0 {
+            continue;
+        }
+        let rank = relative_rank(square, color);
+        let adjacent = adjacent_files_mask(square.file());
+
+        let isolated = own & adjacent == 0;
+        if isolated {
+            add(&mut score, params.isolated);
+        }
+
+        // a friendly pawn beside it or guarding it from behind
+        let phalanx = own & adjacent & rank_mask(square.rank()) != 0;
+        let supported = square
+            .offset(0, -forward)
+            .is_some_and(|behind| own & adjacent & rank_mask(behind.rank()) != 0);
+        if phalanx || supported {
+            add(&mut score, params.connected[rank]);
+        }
+
+        let ahead = ranks_ahead_mask(square.rank(), color);
+        if enemy & (file_mask(square.file()) | adjacent) & ahead == 0 {
+            passed |= 1 << square.index();
+        }
+
+        // no friendly pawn level with or behind it on the next files, and its stop
+        // square is covered by an enemy pawn, so it can never be safely advanced
+        if !isolated && !phalanx && !supported {
+            let not_ahead = !ahead;
+            // an enemy pawn two ranks ahead on the next files guards the stop square
+            let stop_attacked = [-1, 1].iter().any(|&files| {
+                square
+                    .offset(files, 2 * forward)
+                    .is_some_and(|guard| enemy & (1 << guard.index()) != 0)
+            });
+            if own & adjacent & not_ahead == 0 && stop_attacked {
+                add(&mut score, params.backward);
+            }
+        }
+    }
+
+    let mut islands = 0;
+    let mut in_island = false;
+    for file in File::ALL {
+        let occupied = own & file_mask(file) != 0;
+        if occupied {
+            let count = (own & file_mask(file)).count_ones() as i32;
+            score.0 += params.doubled.0 * (count - 1);
+            score.1 += params.doubled.1 * (count - 1);
+        }
+        if occupied && !in_island {
+            islands += 1;
+        }
+        in_island = occupied;
+    }
+    if islands > 1 {
+        score.0 += params.island.0 * (islands - 1);
+        score.1 += params.island.1 * (islands - 1);
+    }
+
+    (score, passed)
+}
+
+pub fn evaluate_pawns(params: &PawnParams, white_pawns: u64, black_pawns: u64) -> PawnEntry {
+    let (white, white_passed) = evaluate_side(params, white_pawns, black_pawns, Color::White);
+    let (black, black_passed) = evaluate_side(params, black_pawns, white_pawns, Color::Black);
+    PawnEntry {
+        white_pawns,
+        black_pawns,
+        white,
+        black,
+        passed_pawns: white_passed | black_passed,
+    }
+}
+
+// passed pawn bonuses of `color`, halved when the pawn is blocked
+pub fn passed_pawn_score(
+    params: &PawnParams,
+    board: &[ChessPiece; 64],
+    entry: &PawnEntry,
+    color: Color,
+) -> (i32, i32) {
+    let mut score = (0, 0);
+    let forward = if color == Color::White { 1 } else { -1 };
+    for square in (0..64).filter_map(Square::new) {
+        if entry.passed_pawns & (1 << square.index()) == 0
+            || piece_color(board[square.index() as usize]) != Some(color)
+        {
+            continue;
+        }
+        let (mut mg, mut eg) = params.passed[relative_rank(square, color)];
+        let stop = square.offset(0, forward);
+        if stop.is_some_and(|stop| board[stop.index() as usize] != ChessPiece::None) {
+            mg = mg * params.blocked_passed_percent / 100;
+            eg = eg * params.blocked_passed_percent / 100;
+        }
+        score.0 += mg;
+        score.1 += eg;
+    }
+    score
+}
+
+// pawn structure of `color` alone, blended by the game phase
+pub fn pawn_structure(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> i32 {
+    let entry = PAWN_TABLE.with(|table| table.borrow_mut().probe(&params.pawns, board));
+    let passed = passed_pawn_score(&params.pawns, board, &entry, color);
+    let side = if color == Color::White {
+        entry.white
+    } else {
+        entry.black
+    };
+    let mg = side.0 + passed.0;
+    let eg = side.1 + passed.1;
+    let phase = phase.clamp(0, MAX_PHASE);
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+// pawn structure of `color` against the opponent's
+pub fn pawn_structure_score(
+    params: &EvalParams,
+    board: &[ChessPiece; 64],
+    color: Color,
+    phase: i32,
+) -> i32 {
+    let opponent = if color == Color::White {
+        Color::Black
+    } else {
+        Color::White
+    };
+    pawn_structure(params, board, color, phase) - pawn_structure(params, board, opponent, phase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::chessboard::piece::fen_to_board;
+
+    #[test]
+    fn test_pawn_structure() {
+        // white: doubled and isolated c pawns, a passed pawn on d6 and two islands
+        let board = fen_to_board("4k3/p4ppp/3P4/8/2P5/2P5/5PPP/4K3 w - - 0 1");
+        let params = EvalParams::default();
+        let entry = evaluate_pawns(
+            &params.pawns,
+            pawn_bitboard(&board, ChessPiece::WPawn),
+            pawn_bitboard(&board, ChessPiece::BPawn),
+        );
+        let bit = |name| 1 << Square::from_algebraic(name).unwrap().index();
+        assert_ne!(entry.passed_pawns & bit("d6"), 0);
+        // the a7 pawn has no white pawn in front of it on the a and b files
+        assert_ne!(entry.passed_pawns & bit("a7"), 0);
+        // the white king side pawns face black pawns on the same files
+        assert_eq!(entry.passed_pawns & bit("g2"), 0);
+
+        let mut table = PawnHashTable::new();
+        assert_eq!(table.probe(&params.pawns, &board), entry);
+        assert_eq!(table.probe(&params.pawns, &board), entry);
+        assert_eq!((table.hits, table.misses), (1, 1));
+        assert!(pawn_structure_score(&params, &board, Color::White, 0) > 0);
+    }
+}
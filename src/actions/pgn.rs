@@ -0,0 +1,297 @@
+use crate::interface::chessboard::piece::{fen_to_board, ChessPiece, Color};
+
+use super::{
+    play::{Game, Move, MoveType},
+    square::{File, Rank, Square},
+};
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PgnGame {
+    pub headers: Vec<(String, String)>,
+    // moves of the main line in standard algebraic notation
+    pub moves: Vec<String>,
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // white's score, 1.0 for a white win, None when the game was not finished
+    pub fn white_score(&self) -> Option<f64> {
+        match self.result.as_str() {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None,
+        }
+    }
+}
+
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+    Some((name.to_string(), value))
+}
+
+// main line moves and result of a movetext section, comments and variations left out
+fn parse_movetext(text: &str, game: &mut PgnGame) {
+    let mut token = String::new();
+    let mut comment = false;
+    let mut line_comment = false;
+    let mut variation_depth = 0;
+    let mut tokens = vec![];
+
+    for c in text.chars() {
+        if line_comment {
+            line_comment = c != '\n';
+            continue;
+        }
+        if comment {
+            comment = c != '}';
+            continue;
+        }
+        match c {
+            '{' => comment = true,
+            ';' => line_comment = true,
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            _ if c.is_whitespace() => tokens.push(std::mem::take(&mut token)),
+            _ => token.push(c),
+        }
+        if variation_depth > 0 || comment || line_comment {
+            tokens.push(std::mem::take(&mut token));
+        }
+    }
+    tokens.push(token);
+
+    for token in tokens {
+        if let "1-0" | "0-1" | "1/2-1/2" | "*" = token.as_str() {
+            game.result = token;
+            continue;
+        }
+        // move numbers can be glued to the move, as in 12.e4 or 12...e5, but the digits
+        // of 0-0 and 0-0-0 are the move itself
+        let digits = token.find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+        let token = match token[digits..].strip_prefix('.') {
+            Some(rest) if digits > 0 => rest.trim_start_matches('.'),
+            _ => token.as_str(),
+        };
+        if !token.is_empty() && !token.starts_with('$') {
+            game.moves.push(token.to_string());
+        }
+    }
+    // movetext that stops without a result keeps the one from the tags
+    if game.result.is_empty() {
+        game.result = game.header("Result").unwrap_or("*").to_string();
+    }
+}
+
+// every game of a pgn file, in the order they appear
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = vec![];
+    let mut game = PgnGame::default();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        if line.trim_start().starts_with('[') {
+            // tags after a movetext start the next game
+            if !movetext.trim().is_empty() {
+                parse_movetext(&movetext, &mut game);
+                games.push(std::mem::take(&mut game));
+                movetext.clear();
+            }
+            if let Some(header) = parse_header(line) {
+                game.headers.push(header);
+            }
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !movetext.trim().is_empty() || !game.headers.is_empty() {
+        parse_movetext(&movetext, &mut game);
+        games.push(game);
+    }
+    games
+}
+
+fn piece_for(letter: char, color: Color) -> ChessPiece {
+    let white = color == Color::White;
+    match (letter, white) {
+        ('K', true) => ChessPiece::WKing,
+        ('K', false) => ChessPiece::BKing,
+        ('Q', true) => ChessPiece::WQueen,
+        ('Q', false) => ChessPiece::BQueen,
+        ('R', true) => ChessPiece::WRook,
+        ('R', false) => ChessPiece::BRook,
+        ('B', true) => ChessPiece::WBishop,
+        ('B', false) => ChessPiece::BBishop,
+        ('N', true) => ChessPiece::WKnight,
+        ('N', false) => ChessPiece::BKnight,
+        (_, true) => ChessPiece::WPawn,
+        (_, false) => ChessPiece::BPawn,
+    }
+}
+
+// the legal move a san string like Nbd7, exd6, e8=Q or O-O-O stands for in this game
+pub fn san_to_move(game: &Game, san: &str) -> Option<Move> {
+    let board = fen_to_board(&game.fen);
+    let parts = game.fen.split(" ").collect::<Vec<&str>>();
+    let color = if parts.get(1) == Some(&"b") {
+        Color::Black
+    } else {
+        Color::White
+    };
+    let castling_rights = parts.get(2).unwrap_or(&"-").to_string();
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let castle_to = match san {
+        "O-O" | "0-0" => Some(File::G),
+        "O-O-O" | "0-0-0" => Some(File::C),
+        _ => None,
+    };
+    if let Some(file) = castle_to {
+        let back = Rank::back(color);
+        let from = Square::at(File::E, back);
+        let move_ = Move {
+            from: from.index(),
+            to: Square::at(file, back).index(),
+            piece: board[from.index() as usize],
+            promote: String::new(),
+            move_type: MoveType::Castle,
+            captured_piece: ChessPiece::None,
+            castling_rights,
+        };
+        return game.clone().make_move(&move_).0.then_some(move_);
+    }
+
+    let (san, promote) = match san.split_once('=') {
+        Some((san, promote)) => (san, promote.to_lowercase()),
+        None => match san.chars().last() {
+            Some(c @ ('Q' | 'R' | 'B' | 'N')) => {
+                (&san[..san.len() - 1], c.to_lowercase().to_string())
+            }
+            _ => (san, String::new()),
+        },
+    };
+    let first = san.chars().next()?;
+    let (piece, rest) = if "KQRBN".contains(first) {
+        (piece_for(first, color), &san[1..])
+    } else {
+        (piece_for('P', color), san)
+    };
+    let rest = rest.replace('x', "");
+    if rest.len() < 2 || !rest.is_ascii() {
+        return None;
+    }
+    let (hint, target) = rest.split_at(rest.len() - 2);
+    let to = Square::from_algebraic(target)?;
+
+    let mut found = None;
+    for (index, square) in board.iter().enumerate() {
+        if *square != piece {
+            continue;
+        }
+        let from = Square::new(index as i32)?;
+        // a file, a rank or both telling apart pieces that reach the same square
+        let from_name = from.to_string();
+        if !hint.chars().all(|c| from_name.contains(c)) {
+            continue;
+        }
+        let is_pawn = piece == ChessPiece::WPawn || piece == ChessPiece::BPawn;
+        let captured_piece = board[to.index() as usize];
+        let move_type = if !promote.is_empty() {
+            MoveType::Promotion
+        } else if is_pawn && from.file() != to.file() && captured_piece == ChessPiece::None {
+            MoveType::EnPassant
+        } else {
+            MoveType::Normal
+        };
+        let move_ = Move {
+            from: from.index(),
+            to: to.index(),
+            piece,
+            promote: promote.clone(),
+            move_type,
+            captured_piece,
+            castling_rights: castling_rights.clone(),
+        };
+        if game.clone().make_move(&move_).0 {
+            // two pieces fitting the same san means the san is ambiguous
+            if found.is_some() {
+                return None;
+            }
+            found = Some(move_);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pgn() {
+        let text = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+[WhiteElo "2100"]
+
+1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 3. Bb5 a6 $1 4. O-O 1-0
+
+[Event "Second"]
+[Result "*"]
+
+1. d4 d5 *
+
+[Event "Third"]
+
+1.e4 1...e5 2.Nf3 Nc6 3.Bc4 Bc5 4.0-0 Nf6 5.d3 0-0 *
+"#;
+        let games = parse_pgn(text);
+        assert_eq!(games.len(), 3);
+        assert_eq!(
+            games[0].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]
+        );
+        assert_eq!(games[0].white_score(), Some(1.0));
+        assert_eq!(games[0].header("WhiteElo"), Some("2100"));
+        assert_eq!(games[1].white_score(), None);
+        // castling written with zeros is not a move number
+        assert_eq!(
+            games[2].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "0-0", "Nf6", "d3", "0-0"]
+        );
+
+        let mut game = Game::new(START_FEN, None);
+        for san in &games[0].moves {
+            let move_ = san_to_move(&game, san).unwrap();
+            assert!(game.make_move(&move_).0, "{}", san);
+        }
+        assert_eq!(
+            game.fen.split(" ").next(),
+            Some("r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQ1RK1")
+        );
+
+        let mut game = Game::new(START_FEN, None);
+        for san in &games[2].moves {
+            let move_ = san_to_move(&game, san).unwrap();
+            assert!(game.make_move(&move_).0, "{}", san);
+        }
+        assert_eq!(
+            game.fen.split(" ").next(),
+            Some("r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQ1RK1")
+        );
+    }
+}
@@ -9,6 +9,7 @@ use super::{
         knight_possible_squares, rook_possible_squares,
     },
     player::Engine,
+    square::{File, Rank, Square},
 };
 
 #[derive(Debug, Clone, PartialEq, godot::prelude::ToVariant, FromVariant)]
@@ -71,8 +72,10 @@ impl Game {
         let mut board_pieces: [ChessPiece; 64] = fen_to_board(&self.fen.clone());
         let enpassant_part = self.fen.split(" ").collect::<Vec<&str>>()[3]; // e6 for example
         let rules_part = self.fen.split(" ").collect::<Vec<&str>>()[1..].join(" ");
-        let enpassant_sqr = (enpassant_part.chars().nth(0).unwrap() as i32 - 97) + // a-h
-        (8 * (7-(enpassant_part.chars().nth(1).unwrap() as i32 - 49))); // 1-8
+        let enpassant_sqr = match Square::from_algebraic(enpassant_part) {
+            Some(square) => square.index(),
+            None => return (self.fen.clone(), false),
+        };
 
         let piece = board_pieces[move_.from as usize];
 
@@ -136,17 +139,10 @@ impl Game {
         }
         if posible_moves.contains(&move_.to) {
             let mut enpassant_string = String::from("-");
-            let starting_rank = match piece.color() {
-                Color::White => 6,
-                Color::Black => 1,
-            };
-            // from_times_moved is 0 if pawn has not moved yet and must have moved 2 squares at start
-            if move_.from / 8 == starting_rank && (move_.to / 8 - move_.from / 8).abs() == 2 {
-                let row_diff = (move_.to / 8) as i32 - (move_.from / 8) as i32;
-                if row_diff.abs() == 2 {
-                    let file = (move_.to % 8) as i32 + 97;
-                    let rank = 8 - (move_.to / 8) as i32;
-                    enpassant_string = format!("{}{}", file as u8 as char, rank);
+            // a pawn that moved 2 squares from its starting rank can be taken en passant
+            if let (Some(from), Some(to)) = (Square::new(move_.from), Square::new(move_.to)) {
+                if from.rank() == Rank::pawn_start(piece.color()) && from.rank_distance(to) == 2 {
+                    enpassant_string = to.to_string();
                 }
             }
             rules_part[2] = &enpassant_string;
@@ -282,14 +278,15 @@ impl Game {
             if piece == ChessPiece::BRook || piece == ChessPiece::WRook {
                 let mut rights = String::from(castling_rights);
                 // check the side of the rook
-                if move_.from % 8 == 0 {
+                let file = Square::new(move_.from).map(Square::file);
+                if file == Some(File::A) {
                     // left rook
                     if piece.color() == Color::White {
                         rights = rights.replace("Q", "");
                     } else {
                         rights = rights.replace("q", "");
                     }
-                } else if move_.from % 8 == 7 {
+                } else if file == Some(File::H) {
                     // right rook
                     if piece.color() == Color::White {
                         rights = rights.replace("K", "");
@@ -313,7 +310,10 @@ impl Game {
     fn queen_move(&self, move_: &Move) -> (String, bool) {
         // check miving like a bishop or rook
 
-        let like_rook = move_.from % 8 == move_.to % 8 || move_.from / 8 == move_.to / 8;
+        let like_rook = match (Square::new(move_.from), Square::new(move_.to)) {
+            (Some(from), Some(to)) => from.file() == to.file() || from.rank() == to.rank(),
+            _ => false,
+        };
         if like_rook {
             let as_rook = self.rook_move(move_);
             if as_rook.1 {
@@ -431,8 +431,11 @@ impl Game {
         if !possible_sqrs.contains(&move_.to) {
             return (self.fen.clone(), false);
         }
-        let col_diff = (move_.to % 8) as i32 - (move_.from % 8) as i32;
-        if col_diff.abs() > 1 {
+        let col_diff = match (Square::new(move_.from), Square::new(move_.to)) {
+            (Some(from), Some(to)) => from.file_distance(to),
+            _ => 0,
+        };
+        if col_diff > 1 {
             return self.castle_move(move_);
         }
         if possible_sqrs.contains(&move_.to) {
@@ -594,7 +597,7 @@ impl Game {
         // let fen_part = fen_part.replace("w", "-");
         let fen = format!("{} {}", fen_part, rules_part);
         self.fen = fen;
-        self.move_notations.push(name_moves(&self.fen, move_));
+        self.move_notations.push(name_moves(move_));
         if state == GameState::Checkmate || state == GameState::Stalemate {
             self.game_over = true;
             self.winner = if state == GameState::Checkmate {
@@ -605,6 +608,7 @@ impl Game {
         }
         return (true, is_check);
     }
+
     pub fn unmake_move(&mut self, move_: &Move) -> bool {
         let is_en_passant = move_.move_type == MoveType::EnPassant;
         let is_castle = move_.move_type == MoveType::Castle;
@@ -614,7 +618,6 @@ impl Game {
         // );
         let mut board_pieces: [ChessPiece; 64] = fen_to_board(&self.fen.clone());
         let rules_part = self.fen.split(" ").collect::<Vec<&str>>()[1..].join(" ");
-        let fen_part = self.fen.split(" ").collect::<Vec<&str>>()[0];
 
         let moving_piece = board_pieces[move_.to as usize];
 
@@ -636,20 +639,25 @@ impl Game {
             board_pieces[rook_from as usize] = board_pieces[rook_to as usize];
             board_pieces[rook_to as usize] = ChessPiece::None;
         }
-        // if enpassant occured return pawn to original position
+        // if enpassant occured return pawn to original position, the captured pawn
+        // stood a rank behind the square the capturing pawn went to
+        let behind = if moving_piece == ChessPiece::WPawn {
+            -1
+        } else {
+            1
+        };
+        let capture_pawn_sqr = Square::new(move_.to).and_then(|to| to.offset(0, behind));
         if is_en_passant {
-            let capture_pawn_sqr = if moving_piece == ChessPiece::BPawn {
-                move_.to + 8
-            } else {
-                move_.to - 8
-            };
             board_pieces[move_.from as usize] = moving_piece;
             board_pieces[move_.to as usize] = ChessPiece::None;
-            board_pieces[capture_pawn_sqr as usize] = if moving_piece == ChessPiece::BPawn {
-                ChessPiece::WPawn
-            } else {
-                ChessPiece::BPawn
-            };
+            if let Some(capture_pawn_sqr) = capture_pawn_sqr {
+                board_pieces[capture_pawn_sqr.index() as usize] =
+                    if moving_piece == ChessPiece::BPawn {
+                        ChessPiece::WPawn
+                    } else {
+                        ChessPiece::BPawn
+                    };
+            }
         }
 
         // if promotion occured return pawn to original position
@@ -669,16 +677,10 @@ impl Game {
         let mut rules_part = rules_part.to_vec();
         let mut enpassant_part = String::from("-");
         if is_en_passant {
-            // reconstruct enpassant part
-            enpassant_part = if moving_piece == ChessPiece::BPawn {
-                let file = (move_.to % 8) as i32 + 97;
-                let rank = 8 - (move_.to / 8) as i32;
-                format!("{}{}", file as u8 as char, rank)
-            } else {
-                let file = (move_.to % 8) as i32 + 97;
-                let rank = 8 - (move_.to / 8) as i32;
-                format!("{}{}", file as u8 as char, rank)
-            };
+            // reconstruct enpassant part, the fen keeps the square of the pawn that was taken
+            if let Some(capture_pawn_sqr) = capture_pawn_sqr {
+                enpassant_part = capture_pawn_sqr.to_string();
+            }
         }
 
         rules_part[2] = &enpassant_part;
@@ -714,7 +716,7 @@ impl Game {
         rules_part[4] = &move_count;
 
         let rules_part = rules_part.join(" ");
-        let fen = format!("{} {}", fen_part, rules_part);
+        let fen = format!("{} {}", board_to_fen(&board_pieces), rules_part);
 
         // update game state
         self.fen = fen;
@@ -726,23 +728,54 @@ impl Game {
     }
 }
 
-pub fn name_moves(fen: &str, move_: &Move) -> String {
-    let board = fen_to_board(&fen);
-    let from_sqr;
-    let from_file;
-    from_file = format!("{}", ((move_.from % 8) as i32 + 97) as u8 as char);
-    let from_rank = 8 - (move_.from / 8) as i32;
-
-    let to_file = (move_.to % 8) as i32 + 97;
-    let to_rank = 8 - (move_.to / 8) as i32;
-
-    if board[move_.to as usize] != ChessPiece::None {
-        from_sqr = format!("{}{}", from_file, from_rank);
-    } else {
-        from_sqr = format!("{}{}", from_file, from_rank);
+// long algebraic notation of the move, such as e2e4
+pub fn name_moves(move_: &Move) -> String {
+    match (Square::new(move_.from), Square::new(move_.to)) {
+        (Some(from), Some(to)) => format!("{}{}", from, to),
+        _ => String::new(),
     }
+}
 
-    let move_note = format!("{}{}{}", from_sqr, to_file as u8 as char, to_rank,);
-
-    move_note
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmake_en_passant() {
+        // e5 takes d6 and e4 takes d3 en passant, undoing each puts the taken pawn back
+        // on its own square and the fen back on the double pushed pawn
+        for (fen, from, to, piece) in [
+            (
+                "4k3/8/8/3pP3/8/8/8/4K3 w - d5 0 1",
+                "e5",
+                "d6",
+                ChessPiece::WPawn,
+            ),
+            (
+                "4k3/8/8/8/3Pp3/8/8/4K3 b - d4 0 1",
+                "e4",
+                "d3",
+                ChessPiece::BPawn,
+            ),
+        ] {
+            let mut game = Game::new(fen, None);
+            let move_ = Move {
+                from: Square::from_algebraic(from).unwrap().index(),
+                to: Square::from_algebraic(to).unwrap().index(),
+                piece,
+                promote: String::new(),
+                move_type: MoveType::EnPassant,
+                captured_piece: if piece == ChessPiece::WPawn {
+                    ChessPiece::BPawn
+                } else {
+                    ChessPiece::WPawn
+                },
+                castling_rights: String::from("-"),
+            };
+            assert!(game.make_move(&move_).0);
+            assert!(game.unmake_move(&move_));
+            let fields = |fen: &str| fen.split(" ").take(4).collect::<Vec<_>>().join(" ");
+            assert_eq!(fields(&game.fen), fields(fen));
+        }
+    }
 }
@@ -1,11 +1,18 @@
 use crate::{
     actions::{
-        path::PossibleMoves,
-        play::{Game, MoveType},
+        movegen::MoveList, packed::PackedMove, path::PossibleMoves, play::Game, position::Position,
     },
     interface::chessboard::piece::{fen_to_board, ChessPiece, Color as PieceColor},
 };
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ChessData {
@@ -15,326 +22,1286 @@ struct ChessData {
 }
 
 use super::{
-    capture::get_pieces_by_color,
-    heatmap::{piece_heat_map, GameType},
+    book::{game_ply, BookSelection, OpeningBook},
+    endgame::{piece_count, Dtm, EndgameTables, MAX_ENDGAME_PIECES},
+    evaluation::{trace_board, EvalTrace},
+    params::eval_params,
     play::Move,
+    transposition::{Bound, TranspositionTable, TtEntry, DEFAULT_HASH_MB},
 };
 
+pub const MATE_SCORE: f64 = 1_000_000_000.0;
+const MAX_PLY: i32 = 64;
+const PV_PLIES: usize = MAX_PLY as usize;
+
+// window around the previous iteration's score, widened on every fail
+const ASPIRATION_WINDOW: f64 = 50.0;
+const MAX_ASPIRATION_WINDOW: f64 = 5000.0;
+
+// margin added to the static evaluation before giving up on quiet moves, by depth
+const FUTILITY_MARGINS: [f64; 3] = [0.0, 200.0, 500.0];
+
+// null-move searches this deep are verified with a normal reduced search (zugzwang guard)
+const NULL_MOVE_VERIFICATION_DEPTH: i32 = 6;
+
+// moves searched before late move reductions start
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+
+// half moves after which the opening book is no longer asked
+const MAX_BOOK_DEPTH: u32 = 16;
+
+// skill levels go from 0, a beginner, to 20, the full strength engine
+pub const MAX_SKILL_LEVEL: u8 = 20;
+// rough strength of skill level 0 and of the full engine, for the elo setting
+const MIN_ELO: u32 = 600;
+const MAX_ELO: u32 = 2000;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EngineOptions {
+    pub depth: i32,
+    pub null_move_pruning: bool,
+    pub late_move_reductions: bool,
+    pub principal_variation_search: bool,
+    pub aspiration_windows: bool,
+    pub check_extensions: bool,
+    pub futility_pruning: bool,
+    pub max_book_depth: u32,
+    pub book_selection: BookSelection,
+    pub skill_level: u8,
+    // overrides skill_level with the level closest to this rating
+    pub target_elo: Option<u32>,
+    pub max_nodes: Option<u64>,
+    // root moves reported by analyse, uci's MultiPV
+    pub multi_pv: usize,
+    // search threads sharing the transposition table, the first one picks the move
+    pub threads: usize,
+    pub hash_mb: usize,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            depth: 3,
+            null_move_pruning: true,
+            late_move_reductions: true,
+            principal_variation_search: true,
+            aspiration_windows: true,
+            check_extensions: true,
+            futility_pruning: true,
+            max_book_depth: MAX_BOOK_DEPTH,
+            book_selection: BookSelection::WeightedRandom,
+            skill_level: MAX_SKILL_LEVEL,
+            target_elo: None,
+            max_nodes: None,
+            multi_pv: 1,
+            threads: 1,
+            hash_mb: DEFAULT_HASH_MB,
+        }
+    }
+}
+
+impl EngineOptions {
+    pub fn effective_skill(&self) -> u8 {
+        self.target_elo
+            .map(skill_for_elo)
+            .unwrap_or(self.skill_level)
+            .min(MAX_SKILL_LEVEL)
+    }
+}
+
+// skill level that plays closest to the rating
+pub fn skill_for_elo(elo: u32) -> u8 {
+    let elo = elo.clamp(MIN_ELO, MAX_ELO);
+    ((elo - MIN_ELO) * MAX_SKILL_LEVEL as u32 / (MAX_ELO - MIN_ELO)) as u8
+}
+
+// how a skill level holds the engine back, nothing at the full level
+#[derive(Debug, PartialEq, Clone)]
+pub struct Weakness {
+    pub depth: i32,
+    pub nodes: Option<u64>,
+    // random centipawns added to every root move before the best one is picked
+    pub noise: f64,
+    // chance of not seeing a capture anywhere below the root
+    pub miss_chance: f64,
+}
+
+impl Weakness {
+    pub fn for_skill(skill: u8, depth: i32) -> Self {
+        let skill = skill.min(MAX_SKILL_LEVEL);
+        if skill == MAX_SKILL_LEVEL {
+            return Weakness {
+                depth,
+                nodes: None,
+                noise: 0.0,
+                miss_chance: 0.0,
+            };
+        }
+        let missing = (MAX_SKILL_LEVEL - skill) as f64;
+        Weakness {
+            depth: (1 + (depth - 1) * skill as i32 / MAX_SKILL_LEVEL as i32).max(1),
+            nodes: Some(500 << (skill / 2)),
+            noise: missing * 15.0,
+            miss_chance: missing / 40.0,
+        }
+    }
+}
+
+// counters from the last search, to compare the selectivity options against each other
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SearchStats {
+    pub depth: i32,
+    pub score: f64,
+    pub nodes: u64,
+    pub null_move_cutoffs: u64,
+    pub reductions: u64,
+    pub re_searches: u64,
+    pub futility_prunes: u64,
+    pub check_extensions: u64,
+    pub tablebase_hits: u64,
+    pub tt_hits: u64,
+    pub see_prunes: u64,
+    // principal variation of the last finished iteration, the best move first
+    pub pv: Vec<PackedMove>,
+}
+
+// a search running on the opponent's time, see Engine::start_ponder
+#[derive(Debug)]
+pub struct Ponder {
+    // reply the search expects from the opponent, and the position after it
+    pub expected: PackedMove,
+    pub position: Game,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<(SearchStats, f64, Option<PackedMove>)>,
+}
+
+impl Ponder {
+    // the same squares with another promotion piece is a different move
+    pub fn is_hit(&self, played: &Move) -> bool {
+        PackedMove::from_move(played) == self.expected
+    }
+}
+
+// one root move with its score and the line the search expects after it
+#[derive(Debug, PartialEq, Clone)]
+pub struct PvLine {
+    pub score: f64,
+    pub moves: Vec<PackedMove>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Engine {
     pub color: PieceColor,
+    pub options: EngineOptions,
+    pub stats: SearchStats,
+    pub book: Option<OpeningBook>,
+    pub endgame_tables: Option<EndgameTables>,
+    pub tt: Arc<TranspositionTable>,
 }
 
 impl Engine {
-    pub fn generate_best_move(&mut self, game: &mut Game, color: PieceColor) -> Move {
-        let (valuation, best_move) =
-            alpha_beta_search(game, 3, std::f64::NEG_INFINITY, std::f64::INFINITY, color);
-        println!("best move: {:?}", best_move);
-        let castling_part = game.fen.split(" ").collect::<Vec<&str>>()[2];
-        if best_move.is_none() {
-            return Move {
-                from: 0,
-                to: 0,
-                piece: ChessPiece::None,
-                promote: "".into(),
-                move_type: MoveType::Normal,
-                captured_piece: ChessPiece::None,
-                castling_rights: castling_part.to_string(),
-            };
+    pub fn new(color: PieceColor) -> Self {
+        Engine {
+            color,
+            options: EngineOptions::default(),
+            stats: SearchStats::default(),
+            book: None,
+            endgame_tables: None,
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
         }
+    }
+
+    pub fn resize_hash(&mut self, megabytes: usize) {
+        self.options.hash_mb = megabytes;
+        self.tt = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    // forget everything searched so far, for a new game
+    pub fn clear_hash(&self) {
+        self.tt.clear();
+    }
 
-        let best_move = best_move.unwrap();
-        let best_move = Move {
-            from: best_move.from,
-            to: best_move.to,
-            piece: best_move.piece,
-            promote: "".into(),
-            move_type: MoveType::Normal,
-            captured_piece: ChessPiece::None,
-            castling_rights: castling_part.to_string(),
+    fn searcher<'a>(&'a self, weakness: &Weakness, stop: Option<&'a AtomicBool>) -> Searcher<'a> {
+        let node_limit = match (weakness.nodes, self.options.max_nodes) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
         };
-        best_move
+        Searcher {
+            options: &self.options,
+            endgame_tables: self.endgame_tables.as_ref(),
+            tt: &self.tt,
+            stop,
+            stats: SearchStats::default(),
+            node_limit,
+            noise: weakness.noise,
+            miss_chance: weakness.miss_chance,
+            pv: [[PackedMove::default(); PV_PLIES]; PV_PLIES],
+            pv_len: [0; PV_PLIES],
+        }
     }
-}
 
-pub fn evaluate_position(game: &Game, color: PieceColor) -> f64 {
-    let board: [ChessPiece; 64] = fen_to_board(&game.fen);
-
-    let my_pieces: Vec<(ChessPiece, i32)> = get_pieces_by_color(&board, color);
-    let opponent_color = if color == PieceColor::White {
-        PieceColor::Black
-    } else {
-        PieceColor::White
-    };
-
-    let opponent_pieces: Vec<(ChessPiece, i32)> = get_pieces_by_color(&board, opponent_color);
-
-    let game_stage = if my_pieces.len() + opponent_pieces.len() < 10 {
-        GameType::End
-    } else if my_pieces.len() + opponent_pieces.len() < 20 {
-        GameType::Middle
-    } else {
-        GameType::Opening
-    };
-    let mut valuation = 0;
-    for (piece, pos) in &my_pieces {
-        let heat_map: [[i32; 8]; 8] = piece_heat_map(*piece, color, &game_stage);
-
-        let row = pos / 8;
-        let col = pos % 8;
-        valuation += heat_map[row as usize][col as usize];
-    }
-
-    for (piece, pos) in &opponent_pieces {
-        let heat_map: [[i32; 8]; 8] = piece_heat_map(*piece, opponent_color, &game_stage);
-
-        let row = pos / 8;
-        let col = pos % 8;
-        if *piece != ChessPiece::None {
-            valuation -= heat_map[row as usize][col as usize];
-        }
-    }
-    let opp_possible_moves = game.possible_moves(opponent_color);
-
-    for mv in opp_possible_moves {
-        let piece = board[mv.to as usize];
-        let capture_penalty = match piece {
-            ChessPiece::WKing | ChessPiece::BKing => 10000,
-            ChessPiece::WQueen | ChessPiece::BQueen => 200,
-            ChessPiece::WRook | ChessPiece::BRook => 180,
-            ChessPiece::WBishop | ChessPiece::BBishop => 150,
-            ChessPiece::WKnight | ChessPiece::BKnight => 150,
-            _ => 80,
+    // tables made by the endgame generator, probed at the root and in the search
+    pub fn load_endgame_tables(&mut self, directory: &str) -> Result<(), String> {
+        self.endgame_tables = Some(EndgameTables::load_dir(directory)?);
+        Ok(())
+    }
+
+    // polyglot .bin book played from before searching
+    pub fn load_book(&mut self, path: &str) -> Result<(), String> {
+        self.book = Some(OpeningBook::load(path)?);
+        Ok(())
+    }
+
+    pub fn book_move(&self, game: &Game) -> Option<Move> {
+        let book = self.book.as_ref()?;
+        if game_ply(&game.fen) >= self.options.max_book_depth {
+            return None;
+        }
+        book.choose_move(game, self.options.book_selection)
+    }
+
+    // the move to play, with everything make_move and the board need to play it right;
+    // None when the side to move has no legal move left
+    pub fn generate_best_move(&mut self, game: &Game, color: PieceColor) -> Option<Move> {
+        if let Some(book_move) = self.book_move(game) {
+            return Some(book_move);
+        }
+        let (_, best_move) = self.search(game, color);
+        best_move.map(|mv| mv.to_move(game))
+    }
+
+    // tables first, then iterative deepening on as many threads as the options ask for
+    pub fn search(&mut self, game: &Game, color: PieceColor) -> (f64, Option<PackedMove>) {
+        self.search_until(game, color, &AtomicBool::new(false))
+    }
+
+    // search that gives up as soon as `stop` is raised, keeping the last finished iteration
+    pub fn search_until(
+        &mut self,
+        game: &Game,
+        color: PieceColor,
+        stop: &AtomicBool,
+    ) -> (f64, Option<PackedMove>) {
+        if let Some((mv, dtm)) = self
+            .endgame_tables
+            .as_ref()
+            .and_then(|tables| tables.best_move(game))
+        {
+            let score = endgame_score(dtm, 0);
+            self.stats = SearchStats {
+                score,
+                tablebase_hits: 1,
+                ..Default::default()
+            };
+            return (
+                score,
+                Some(PackedMove::from_possible(&fen_to_board(&game.fen), &mv)),
+            );
+        }
+        let weakness = Weakness::for_skill(self.options.effective_skill(), self.options.depth);
+        // lazy smp: helpers search the same root, some of them a ply deeper, and only share
+        // what they find through the transposition table
+        let helpers = if weakness.noise == 0.0 {
+            self.options.threads.max(1) - 1
+        } else {
+            0
         };
-        if mv.captured_piece != ChessPiece::None {
-            let piece = board[mv.to as usize];
-            if piece != ChessPiece::None {
-                valuation -= piece.piece_value() * capture_penalty;
+        let done = AtomicBool::new(false);
+        let (score, best_move, stats) = thread::scope(|scope| {
+            let workers = (1..=helpers)
+                .map(|helper| {
+                    let mut searcher = self.searcher(&weakness, Some(&done));
+                    let depth = weakness.depth + (helper % 2) as i32;
+                    scope.spawn(move || {
+                        let mut position = Position::from_fen(&game.fen);
+                        searcher.iterate(&mut position, color, 1 + (helper % 2) as i32, depth);
+                        searcher.stats
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut searcher = self.searcher(&weakness, Some(stop));
+            let mut position = Position::from_fen(&game.fen);
+            let (score, best_move) = searcher.iterate(&mut position, color, 1, weakness.depth);
+            done.store(true, Ordering::Relaxed);
+            for worker in workers {
+                if let Ok(stats) = worker.join() {
+                    searcher.stats.nodes += stats.nodes;
+                }
             }
+            (score, best_move, searcher.stats)
+        });
+
+        self.stats = SearchStats { score, ..stats };
+        (score, best_move)
+    }
+
+    // searches the position after the reply the last search expected, on a thread of its
+    // own, while the opponent thinks; `game` is the position after this engine's move and
+    // None comes back when the last search had no reply in its line
+    pub fn start_ponder(&self, game: &Game) -> Option<Ponder> {
+        let expected = *self.stats.pv.get(1)?;
+        let mut position = game.clone();
+        if !position.make_move(&expected.to_move(game)).0 {
+            return None;
         }
+        // the ponder thread works on a copy, the transposition table is shared with it
+        let mut engine = self.clone();
+        let color = if position.fen.split(" ").nth(1) == Some("b") {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_position = position.clone();
+        let handle = thread::spawn(move || {
+            let (score, mv) = engine.search_until(&thread_position, color, &thread_stop);
+            (engine.stats, score, mv)
+        });
+        Some(Ponder {
+            expected,
+            position,
+            stop,
+            handle,
+        })
     }
 
-    let board_table: [[ChessPiece; 8]; 8] = [[ChessPiece::None; 8]; 8];
+    // the opponent played the expected move: the ponder search is finished to the configured
+    // depth and its result taken over as if this engine had searched the position itself;
+    // with no clock to stop it early the time the opponent spent is simply time saved
+    pub fn ponder_hit(&mut self, ponder: Ponder) -> (f64, Option<PackedMove>) {
+        match ponder.handle.join() {
+            Ok((stats, score, mv)) => {
+                self.stats = stats;
+                (score, mv)
+            }
+            Err(_) => (0.0, None),
+        }
+    }
 
-    let mut rook_bonus = 0;
-    if game_stage == GameType::Middle {
-        for (piece, pos) in &my_pieces {
-            if *piece == ChessPiece::WRook || *piece == ChessPiece::BRook {
-                let col = pos % 8;
+    // the opponent played something else, the ponder search is stopped and thrown away;
+    // whatever it stored in the transposition table is still right for its positions
+    pub fn ponder_miss(&mut self, ponder: Ponder) {
+        ponder.stop.store(true, Ordering::Relaxed);
+        let _ = ponder.handle.join();
+    }
 
-                let mut open_file = true;
-                for i in 0..8 {
-                    if board_table[i][col as usize] != ChessPiece::None {
-                        open_file = false;
-                        break;
-                    }
-                }
+    // the best multi_pv root moves, best first, each with its score and principal
+    // variation; always at full strength since it is meant for hints and reviews
+    pub fn analyse(&mut self, game: &Game, color: PieceColor) -> Vec<PvLine> {
+        let weakness = Weakness::for_skill(MAX_SKILL_LEVEL, self.options.depth);
+        let mut searcher = self.searcher(&weakness, None);
+        let count = self.options.multi_pv.max(1);
+        let mut position = Position::from_fen(&game.fen);
+        let mut lines: Vec<PvLine> = vec![];
+        for depth in 1..=self.options.depth.max(1) {
+            let pv_move = lines.first().and_then(|line| line.moves.first()).copied();
+            let iteration = searcher.root_lines(&mut position, color, depth, count, pv_move);
+            if searcher.out_of_nodes() && !lines.is_empty() {
+                break;
+            }
+            lines = iteration;
+            searcher.stats.depth = depth;
+        }
 
-                // Check if the square is occupied by an opponent piece
-                let square_occupied_by_opponent = opponent_pieces
-                    .iter()
-                    .any(|(_, opponent_pos)| *opponent_pos == *pos);
+        searcher.stats.score = lines.first().map_or(0.0, |line| line.score);
+        self.stats = searcher.stats;
+        lines
+    }
+}
+
+// the alpha-beta window a node is searched with, its distance from the root and whether a
+// null move may be tried there
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    alpha: f64,
+    beta: f64,
+    ply: i32,
+    allow_null: bool,
+}
+
+impl Node {
+    fn new(alpha: f64, beta: f64, ply: i32) -> Self {
+        Node {
+            alpha,
+            beta,
+            ply,
+            allow_null: true,
+        }
+    }
+}
 
-                if open_file {
-                    rook_bonus += 20;
+struct Searcher<'a> {
+    options: &'a EngineOptions,
+    endgame_tables: Option<&'a EndgameTables>,
+    tt: &'a TranspositionTable,
+    // raised when the main thread is done, helper threads stop as if out of nodes
+    stop: Option<&'a AtomicBool>,
+    stats: SearchStats,
+    node_limit: Option<u64>,
+    noise: f64,
+    miss_chance: f64,
+    // triangular table of the principal variation found below each ply, row `ply` holds
+    // pv_len[ply] moves starting with the best move of that ply
+    pv: [[PackedMove; PV_PLIES]; PV_PLIES],
+    pv_len: [usize; PV_PLIES],
+}
+
+impl<'a> Searcher<'a> {
+    // iterative deepening, each iteration searched inside an aspiration window
+    fn iterate(
+        &mut self,
+        position: &mut Position,
+        color: PieceColor,
+        start_depth: i32,
+        max_depth: i32,
+    ) -> (f64, Option<PackedMove>) {
+        let mut score = 0.0;
+        let mut best_move: Option<PackedMove> = None;
+
+        'deepening: for depth in start_depth..=max_depth.max(1) {
+            let mut window = ASPIRATION_WINDOW;
+            // noisy root scores would keep falling outside any window
+            let aspiration = self.options.aspiration_windows && self.noise == 0.0;
+            let (mut alpha, mut beta) = if aspiration && depth > 1 {
+                (score - window, score + window)
+            } else {
+                (-f64::INFINITY, f64::INFINITY)
+            };
+
+            loop {
+                let (value, mv) = self.root_search(position, color, depth, alpha, beta, best_move);
+                // an iteration cut short by the node limit is only trusted when there is
+                // nothing better
+                if self.out_of_nodes() && best_move.is_some() {
+                    break 'deepening;
+                }
+                let failed = (value <= alpha && alpha > -f64::INFINITY)
+                    || (value >= beta && beta < f64::INFINITY);
+                if failed && mv.is_some() {
+                    // fell outside the window, widen it and search again
+                    self.stats.re_searches += 1;
+                    window *= 4.0;
+                    if window > MAX_ASPIRATION_WINDOW {
+                        alpha = -f64::INFINITY;
+                        beta = f64::INFINITY;
+                    } else if value <= alpha {
+                        alpha = score - window;
+                    } else {
+                        beta = score + window;
+                    }
+                    continue;
                 }
-                if square_occupied_by_opponent {
-                    rook_bonus += 10;
+                score = value;
+                if mv.is_some() {
+                    best_move = mv;
+                    self.stats.pv = self.root_pv();
                 }
+                break;
             }
+            self.stats.depth = depth;
+        }
+
+        self.stats.score = score;
+        (score, best_move)
+    }
+
+    // nodes at the ply limit have no line below them and no row of their own
+    fn clear_pv(&mut self, ply: i32) {
+        if let Some(len) = self.pv_len.get_mut(ply as usize) {
+            *len = 0;
         }
     }
 
-    // Add rook bonuses to the overall valuation
-    valuation += rook_bonus;
+    // mv followed by the line found below it, copied up from the next row
+    fn update_pv(&mut self, ply: i32, mv: PackedMove) {
+        let ply = ply as usize;
+        let below = self
+            .pv_len
+            .get(ply + 1)
+            .map_or(0, |len| (*len).min(PV_PLIES - 1));
+        let (rows, next) = self.pv.split_at_mut(ply + 1);
+        let line = &mut rows[ply];
+        line[0] = mv;
+        if let Some(next) = next.first() {
+            line[1..=below].copy_from_slice(&next[..below]);
+        }
+        self.pv_len[ply] = below + 1;
+    }
+
+    // the principal variation from the root
+    fn root_pv(&self) -> Vec<PackedMove> {
+        self.pv[0][..self.pv_len[0]].to_vec()
+    }
 
-    let my_possible_moves = game.possible_moves(color);
-    for mv in my_possible_moves {
-        let piece = board[mv.to as usize];
-        let capture_penalty = match piece {
-            ChessPiece::WKing | ChessPiece::BKing => 10000,
-            ChessPiece::WQueen | ChessPiece::BQueen => 200,
-            ChessPiece::WRook | ChessPiece::BRook => 180,
-            ChessPiece::WBishop | ChessPiece::BBishop => 150,
-            ChessPiece::WKnight | ChessPiece::BKnight => 150,
-            _ => 80,
+    // every root move that can still make the top `count` gets an exact score
+    fn root_lines(
+        &mut self,
+        position: &mut Position,
+        color: PieceColor,
+        depth: i32,
+        count: usize,
+        pv_move: Option<PackedMove>,
+    ) -> Vec<PvLine> {
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
         };
-        if mv.captured_piece != ChessPiece::None {
-            let piece = board[mv.to as usize];
-            if piece != ChessPiece::None {
-                valuation += piece.piece_value() * capture_penalty;
+        let mut moves = MoveList::new();
+        position.generate_moves(&mut moves);
+        order_moves(&mut moves, &position.board, pv_move);
+        self.clear_pv(0);
+
+        let mut lines: Vec<PvLine> = vec![];
+        for mv in moves.iter().copied() {
+            let undo = match position.make_move(mv) {
+                Some(undo) => undo,
+                None => continue,
+            };
+            // moves that cannot beat the last line only need to prove that
+            let alpha = if lines.len() >= count {
+                lines[count - 1].score
+            } else {
+                -f64::INFINITY
+            };
+            let value = -self.negamax(
+                position,
+                opponent,
+                depth - 1,
+                Node::new(-f64::INFINITY, -alpha, 1),
+            );
+            position.unmake_move(mv, undo);
+            if lines.len() < count || value > alpha {
+                self.update_pv(0, mv);
+                lines.push(PvLine {
+                    score: value,
+                    moves: self.root_pv(),
+                });
+                lines.sort_by(|a, b| b.score.total_cmp(&a.score));
+                lines.truncate(count);
             }
         }
+        lines
     }
-    println!("valuation: {}", valuation);
-    valuation as f64
-}
 
-fn evaluate_and_search(
-    game: &mut Game,
-    depth: i32,
-    mut alpha: f64,
-    mut beta: f64,
-    color: PieceColor,
-    mv: PossibleMoves,
-) -> (f64, Option<PossibleMoves>) {
-    let mut best_value = if color == PieceColor::White {
-        std::f64::NEG_INFINITY
-    } else {
-        std::f64::INFINITY
-    };
-
-    let mut best_move = None; // Keep track of the best move.
-    let castling_part = game.fen.split(" ").collect::<Vec<&str>>()[2];
-    if !mv.promote {
-        let move_ = Move {
-            from: mv.from,
-            to: mv.to,
-            piece: mv.piece,
-            promote: "".into(),
-            move_type: MoveType::Normal,
-            captured_piece: mv.captured_piece,
-            castling_rights: castling_part.to_string(),
+    fn out_of_nodes(&self) -> bool {
+        self.node_limit
+            .is_some_and(|limit| self.stats.nodes >= limit)
+            || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    // weaker levels overlook some captures, theirs and their opponent's
+    fn misses(&self, mv: PackedMove, board: &[ChessPiece; 64]) -> bool {
+        self.miss_chance > 0.0
+            && mv.is_capture(board)
+            && rand::thread_rng().gen_bool(self.miss_chance)
+    }
+
+    fn root_search(
+        &mut self,
+        position: &mut Position,
+        color: PieceColor,
+        depth: i32,
+        mut alpha: f64,
+        beta: f64,
+        pv_move: Option<PackedMove>,
+    ) -> (f64, Option<PackedMove>) {
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
         };
-        let (moved, check) = game.make_move(&move_);
+        let mut moves = MoveList::new();
+        position.generate_moves(&mut moves);
+        order_moves(&mut moves, &position.board, pv_move);
 
-        if !moved {
-            // If the move puts the opponent in check, we want to search deeper
-            let (value, new_best_move) = alpha_beta_search(
-                game,
-                depth,
-                alpha.clone(),
-                beta.clone(),
-                if color == PieceColor::White {
-                    PieceColor::Black
-                } else {
-                    PieceColor::White
-                },
-            );
-            let value = value as f64;
-            if color == PieceColor::White && value > best_value {
-                best_value = value;
-                best_move = new_best_move;
-                alpha = alpha.max(best_value);
-            } else if color == PieceColor::Black && value < best_value {
+        let mut best_value = -f64::INFINITY;
+        let mut best_rank = -f64::INFINITY;
+        self.clear_pv(0);
+        let mut best_move = None;
+        let mut searched = 0;
+        for mv in moves.iter().copied() {
+            let undo = match position.make_move(mv) {
+                Some(undo) => undo,
+                None => continue,
+            };
+
+            let value = if self.noise > 0.0 {
+                // every move needs a real score to be picked by its noisy one
+                -self.negamax(
+                    position,
+                    opponent,
+                    depth - 1,
+                    Node::new(-f64::INFINITY, f64::INFINITY, 1),
+                )
+            } else if searched == 0 || !self.options.principal_variation_search {
+                -self.negamax(position, opponent, depth - 1, Node::new(-beta, -alpha, 1))
+            } else {
+                self.zero_window_search(position, color, depth - 1, alpha, beta, 1)
+            };
+            position.unmake_move(mv, undo);
+            searched += 1;
+
+            let rank = if self.noise > 0.0 {
+                value + rand::thread_rng().gen_range(-self.noise..=self.noise)
+            } else {
+                value
+            };
+            if rank > best_rank {
+                best_rank = rank;
                 best_value = value;
-                best_move = new_best_move;
-                beta = beta.min(best_value);
+                self.update_pv(0, mv);
+                best_move = Some(mv);
+            }
+            if value > alpha {
+                alpha = value;
+            }
+            if alpha >= beta {
+                break;
             }
         }
+        (best_value, best_move)
+    }
 
-        let (value, _) = alpha_beta_search(
-            game,
-            depth - 1,
-            alpha.clone(),
-            beta.clone(),
-            if color == PieceColor::White {
-                PieceColor::Black
-            } else {
-                PieceColor::White
-            },
-        );
-        let value = value as f64;
-
-        if color == PieceColor::White && value > best_value {
-            best_value = value;
-            best_move = Some(mv);
-            alpha = alpha.max(best_value);
-        } else if color == PieceColor::Black && value < best_value {
-            best_value = value;
-            best_move = Some(mv);
-            beta = beta.min(best_value);
-        }
-
-        if beta <= alpha {
-            return (best_value, best_move); // Beta cutoff
-        }
-    } else {
-        // Handle promotion by considering all four promotion pieces (queen, rook, knight, bishop)
-        let promotion_pieces = ["q", "r", "n", "b"];
-        let castling_part = game.fen.split(" ").collect::<Vec<&str>>()[2];
-        for promote_piece in &promotion_pieces {
-            let mv = mv.clone();
-            let promoted_move = Move {
-                from: mv.from,
-                to: mv.to,
-                piece: mv.piece,
-                promote: promote_piece.to_string(),
-                move_type: MoveType::Promotion,
-                captured_piece: mv.captured_piece,
-                castling_rights: castling_part.to_string(),
+    // search a non-first move with a null window and re-search it if it turns out better
+    fn zero_window_search(
+        &mut self,
+        child: &mut Position,
+        color: PieceColor,
+        depth: i32,
+        alpha: f64,
+        beta: f64,
+        ply: i32,
+    ) -> f64 {
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        let value = -self.negamax(child, opponent, depth, Node::new(-alpha - 1.0, -alpha, ply));
+        if value > alpha && value < beta {
+            self.stats.re_searches += 1;
+            return -self.negamax(child, opponent, depth, Node::new(-beta, -alpha, ply));
+        }
+        value
+    }
+
+    fn negamax(
+        &mut self,
+        position: &mut Position,
+        color: PieceColor,
+        mut depth: i32,
+        node: Node,
+    ) -> f64 {
+        let Node {
+            mut alpha,
+            beta,
+            ply,
+            allow_null,
+        } = node;
+        self.clear_pv(ply);
+        // the board as it is before any move of this node, for looking at the moves
+        let board = position.board;
+        let checked = position.in_check();
+
+        if checked && self.options.check_extensions && ply < MAX_PLY {
+            // never drop into the quiescence search while in check
+            self.stats.check_extensions += 1;
+            depth += 1;
+        }
+
+        if depth <= 0 || ply >= MAX_PLY {
+            return self.quiescence(position, color, alpha, beta, ply);
+        }
+        self.stats.nodes += 1;
+        if self.out_of_nodes() {
+            return position.evaluate(color) as f64;
+        }
+
+        // exact result from the endgame tables, no need to search any further
+        if let Some(tables) = self.endgame_tables {
+            if piece_count(&board) <= MAX_ENDGAME_PIECES {
+                if let Some(dtm) = tables.probe(&board, color == PieceColor::White) {
+                    self.stats.tablebase_hits += 1;
+                    return endgame_score(dtm, ply);
+                }
+            }
+        }
+
+        // a deep enough earlier result decides the node, except on the principal variation
+        // where the line is still needed
+        let key = position.key();
+        let tt_entry = self.tt.probe(key, ply);
+        if let Some(entry) = tt_entry {
+            let pv_node = beta - alpha > 1.0;
+            let usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
             };
+            if !pv_node && entry.depth >= depth && usable {
+                self.stats.tt_hits += 1;
+                return entry.score;
+            }
+        }
+        let original_alpha = alpha;
 
-            let game = &mut game.clone();
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        let static_eval = if checked {
+            -f64::INFINITY
+        } else {
+            position.evaluate(color) as f64
+        };
 
-            let (value, new_best_move) = alpha_beta_search(
-                game,
-                depth - 1,
-                alpha.clone(),
-                beta.clone(),
-                if color == PieceColor::White {
-                    PieceColor::Black
-                } else {
-                    PieceColor::White
+        // null move: if passing still fails high the position is good enough to cut
+        if self.options.null_move_pruning
+            && allow_null
+            && !checked
+            && depth >= 3
+            && static_eval >= beta
+            && position.has_non_pawn_material(color)
+        {
+            let reduction = if depth > 6 { 3 } else { 2 };
+            let undo = position.make_null_move();
+            let value = -self.negamax(
+                position,
+                opponent,
+                depth - 1 - reduction,
+                Node {
+                    allow_null: false,
+                    ..Node::new(-beta, -beta + 1.0, ply + 1)
                 },
             );
-            let value = value as f64;
-            if color == PieceColor::White && value > best_value {
-                best_value = value;
-                best_move = new_best_move;
-                alpha = alpha.max(best_value);
-            } else if color == PieceColor::Black && value < best_value {
+            position.unmake_null_move(undo);
+            if value >= beta {
+                // deep cutoffs are verified without null moves in case of zugzwang
+                let verified = depth < NULL_MOVE_VERIFICATION_DEPTH
+                    || self.negamax(
+                        position,
+                        color,
+                        depth - reduction,
+                        Node {
+                            allow_null: false,
+                            ..Node::new(beta - 1.0, beta, ply)
+                        },
+                    ) >= beta;
+                if verified {
+                    self.stats.null_move_cutoffs += 1;
+                    // mate scores found after a pass are not proven
+                    return if value >= MATE_SCORE - MAX_PLY as f64 {
+                        beta
+                    } else {
+                        value
+                    };
+                }
+            }
+        }
+
+        let futility = self.options.futility_pruning
+            && !checked
+            && (depth as usize) < FUTILITY_MARGINS.len()
+            && static_eval + FUTILITY_MARGINS[depth as usize] <= alpha;
+
+        let mut moves = StagedMoves::new(tt_entry.and_then(|entry| entry.best_move));
+
+        let mut best_value = -f64::INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        let mut missed = false;
+        while let Some(mv) = moves.next(position) {
+            let undo = match position.make_move(mv) {
+                Some(undo) => undo,
+                None => continue,
+            };
+            let gives_check = position.in_check();
+            if self.misses(mv, &board) {
+                position.unmake_move(mv, undo);
+                missed = true;
+                continue;
+            }
+            legal_moves += 1;
+
+            let quiet = !mv.is_capture(&board) && mv.promotion().is_none();
+            if futility && quiet && !gives_check && legal_moves > 1 {
+                position.unmake_move(mv, undo);
+                self.stats.futility_prunes += 1;
+                continue;
+            }
+
+            let value = if legal_moves == 1 || !self.options.principal_variation_search {
+                -self.negamax(
+                    position,
+                    opponent,
+                    depth - 1,
+                    Node::new(-beta, -alpha, ply + 1),
+                )
+            } else {
+                let reduction = if self.options.late_move_reductions
+                    && legal_moves > LMR_FULL_DEPTH_MOVES
+                    && depth >= 3
+                    && quiet
+                    && !checked
+                    && !gives_check
+                {
+                    self.stats.reductions += 1;
+                    if legal_moves > 2 * LMR_FULL_DEPTH_MOVES && depth >= 6 {
+                        2
+                    } else {
+                        1
+                    }
+                } else {
+                    0
+                };
+
+                let mut value = -self.negamax(
+                    position,
+                    opponent,
+                    depth - 1 - reduction,
+                    Node::new(-alpha - 1.0, -alpha, ply + 1),
+                );
+                if reduction > 0 && value > alpha {
+                    // the reduced search beat alpha, try again at full depth
+                    self.stats.re_searches += 1;
+                    value = -self.negamax(
+                        position,
+                        opponent,
+                        depth - 1,
+                        Node::new(-alpha - 1.0, -alpha, ply + 1),
+                    );
+                }
+                if value > alpha && value < beta {
+                    self.stats.re_searches += 1;
+                    value = -self.negamax(
+                        position,
+                        opponent,
+                        depth - 1,
+                        Node::new(-beta, -alpha, ply + 1),
+                    );
+                }
+                value
+            };
+            position.unmake_move(mv, undo);
+
+            if value > best_value {
                 best_value = value;
-                best_move = new_best_move;
-                beta = beta.min(best_value);
+                best_move = Some(mv);
+            }
+            if value > alpha {
+                alpha = value;
+                self.update_pv(ply, mv);
             }
+            if alpha >= beta {
+                break;
+            }
+        }
 
-            if beta <= alpha {
-                break; // Beta cutoff
+        if legal_moves == 0 {
+            // only overlooked moves is not a mate, just a position it did not understand
+            return if missed {
+                position.evaluate(color) as f64
+            } else if checked {
+                -MATE_SCORE + ply as f64
+            } else {
+                0.0
+            };
+        }
+
+        // stopped searches and weakened ones that overlook moves would only spoil the table
+        if best_value.is_finite() && !self.out_of_nodes() && self.miss_chance == 0.0 {
+            let bound = if best_value >= beta {
+                Bound::Lower
+            } else if best_value > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+            self.tt.store(
+                key,
+                ply,
+                TtEntry {
+                    score: best_value,
+                    depth,
+                    bound,
+                    best_move,
+                },
+            );
+        }
+        best_value
+    }
+
+    // only captures are searched so the evaluation is never taken in the middle of an exchange
+    fn quiescence(
+        &mut self,
+        position: &mut Position,
+        color: PieceColor,
+        mut alpha: f64,
+        beta: f64,
+        ply: i32,
+    ) -> f64 {
+        self.clear_pv(ply);
+        self.stats.nodes += 1;
+        let stand_pat = position.evaluate(color) as f64;
+        if stand_pat >= beta || ply >= MAX_PLY {
+            return stand_pat;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let board = position.board;
+        let mut captures = MoveList::new();
+        position.generate_captures(&mut captures);
+        order_moves(&mut captures, &board, None);
+
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        for mv in captures.iter().copied() {
+            if self.misses(mv, &board) {
+                continue;
+            }
+            // captures that lose material once every recapture is played out are not tried
+            if position.see(mv) < 0 {
+                self.stats.see_prunes += 1;
+                continue;
+            }
+            let undo = match position.make_move(mv) {
+                Some(undo) => undo,
+                None => continue,
+            };
+            let value = -self.quiescence(position, opponent, -beta, -alpha, ply + 1);
+            position.unmake_move(mv, undo);
+            if value >= beta {
+                return value;
+            }
+            if value > alpha {
+                alpha = value;
             }
         }
+        alpha
     }
+}
 
-    (best_value, best_move)
+// the generated tables know the distance to mate, so their wins score like real mates
+fn endgame_score(dtm: Dtm, ply: i32) -> f64 {
+    let plies = (ply as u32 + dtm.plies()) as f64;
+    match dtm {
+        Dtm::Win(_) => MATE_SCORE - plies,
+        Dtm::Loss(_) => -MATE_SCORE + plies,
+        Dtm::Draw => 0.0,
+    }
 }
 
-pub fn alpha_beta_search(
-    game: &mut Game,
-    depth: i32,
-    mut alpha: f64,
-    mut beta: f64,
-    color: PieceColor,
-) -> (f64, Option<PossibleMoves>) {
-    if depth == 0 {
-        return (evaluate_position(game, color), None);
+// the full move for one given by its squares, see PackedMove::from_possible
+pub fn to_move(game: &Game, mv: &PossibleMoves) -> Move {
+    PackedMove::from_possible(&fen_to_board(&game.fen), mv).to_move(game)
+}
+
+// previous best move first, then captures by most valuable victim / least valuable attacker
+fn order_moves(moves: &mut [PackedMove], board: &[ChessPiece; 64], pv_move: Option<PackedMove>) {
+    moves.sort_by_key(|mv| {
+        if pv_move == Some(*mv) {
+            return i32::MIN;
+        }
+        let captured_piece = mv.captured_piece(board);
+        if captured_piece != ChessPiece::None {
+            let piece = board[mv.from().index() as usize];
+            return -(1000 + captured_piece.piece_value() * 10 - piece.piece_value());
+        }
+        if mv.promotion().is_some() {
+            return -500;
+        }
+        0
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    TtMove,
+    Captures,
+    Quiets,
+    Done,
+}
+
+// the moves of a node a stage at a time: the table's move, the captures best victim first
+// and then the quiet moves, each list only generated once it is reached so a cutoff on the
+// table's move or a capture never pays for the quiet moves
+struct StagedMoves {
+    stage: Stage,
+    tt_move: Option<PackedMove>,
+    captures: Option<MoveList>,
+    quiets: Option<MoveList>,
+    index: usize,
+}
+
+impl StagedMoves {
+    fn new(tt_move: Option<PackedMove>) -> Self {
+        StagedMoves {
+            stage: Stage::TtMove,
+            tt_move,
+            captures: None,
+            quiets: None,
+            index: 0,
+        }
+    }
+
+    fn captures(&mut self, position: &Position) -> &MoveList {
+        self.captures.get_or_insert_with(|| {
+            let mut captures = MoveList::new();
+            position.generate_captures(&mut captures);
+            order_moves(&mut captures, &position.board, None);
+            captures
+        })
+    }
+
+    fn quiets(&mut self, position: &Position) -> &MoveList {
+        self.quiets.get_or_insert_with(|| {
+            let mut quiets = MoveList::new();
+            position.generate_quiets(&mut quiets);
+            // promotions first
+            order_moves(&mut quiets, &position.board, None);
+            quiets
+        })
+    }
+
+    fn next(&mut self, position: &Position) -> Option<PackedMove> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::Captures;
+                    // the table's move can belong to another position with the same key, it
+                    // is only played when this position has it as well
+                    if let Some(mv) = self.tt_move {
+                        let found = if mv.is_capture(&position.board) {
+                            self.captures(position).contains(&mv)
+                        } else {
+                            self.quiets(position).contains(&mv)
+                        };
+                        if found {
+                            return Some(mv);
+                        }
+                        self.tt_move = None;
+                    }
+                }
+                Stage::Captures => {
+                    let index = self.index;
+                    match self.captures(position).get(index).copied() {
+                        Some(mv) => {
+                            self.index += 1;
+                            if self.tt_move != Some(mv) {
+                                return Some(mv);
+                            }
+                        }
+                        None => {
+                            self.stage = Stage::Quiets;
+                            self.index = 0;
+                        }
+                    }
+                }
+                Stage::Quiets => {
+                    let index = self.index;
+                    match self.quiets(position).get(index).copied() {
+                        Some(mv) => {
+                            self.index += 1;
+                            if self.tt_move != Some(mv) {
+                                return Some(mv);
+                            }
+                        }
+                        None => self.stage = Stage::Done,
+                    }
+                }
+                Stage::Done => return None,
+            }
+        }
     }
+}
+
+pub fn evaluate_position(game: &Game, color: PieceColor) -> f64 {
+    trace_position(game, color).score as f64
+}
+
+// same evaluation as evaluate_position, with every term broken down per side
+pub fn trace_position(game: &Game, color: PieceColor) -> EvalTrace {
+    trace_board(&eval_params(), &fen_to_board(&game.fen), color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::play::MoveType;
 
-    let mut best_value = if color == PieceColor::White {
-        -f64::INFINITY
-    } else {
-        f64::INFINITY
-    };
+    #[test]
+    fn test_skill_levels() {
+        assert_eq!(skill_for_elo(0), 0);
+        assert_eq!(skill_for_elo(1300), 10);
+        assert_eq!(skill_for_elo(3000), MAX_SKILL_LEVEL);
 
-    let mut best_move = None; // Keep track of the best move.
+        let full = Weakness::for_skill(MAX_SKILL_LEVEL, 5);
+        assert_eq!((full.depth, full.nodes, full.noise), (5, None, 0.0));
+        let beginner = Weakness::for_skill(0, 5);
+        assert_eq!(beginner.depth, 1);
+        assert!(beginner.noise > 0.0 && beginner.miss_chance > 0.0);
 
-    let moves = game.possible_moves(color);
+        let options = EngineOptions {
+            skill_level: 3,
+            target_elo: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(options.effective_skill(), MAX_SKILL_LEVEL);
+
+        // every level still comes up with a legal move
+        let game = Game::new(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            None,
+        );
+        let mut engine = Engine::new(PieceColor::Black);
+        engine.options.skill_level = 0;
+        let (_, mv) = engine.search(&game, PieceColor::Black);
+        let mv = mv.unwrap();
+        assert!(game.clone().make_move(&mv.to_move(&game)).0);
+    }
 
-    for mv in moves {
-        let (value, new_best_move) = evaluate_and_search(game, depth, alpha, beta, color, mv);
-        let value = value as f64;
-        if color == PieceColor::White && value > best_value {
-            best_value = value;
-            best_move = new_best_move;
-            alpha = alpha.max(best_value);
-        } else if color == PieceColor::Black && value < best_value {
-            best_value = value;
-            best_move = new_best_move;
-            beta = beta.min(best_value);
+    #[test]
+    fn test_selective_search() {
+        // mate in two with every pruning on: Nf6+ gxf6 Bxf7 mate
+        let game = Game::new(
+            "r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1",
+            None,
+        );
+        let mut engine = Engine::new(PieceColor::White);
+        engine.options.depth = 4;
+        let (score, mv) = engine.search(&game, PieceColor::White);
+        assert_eq!(mv.map(|mv| mv.to_string()), Some(String::from("d5f6")));
+        assert_eq!(score, MATE_SCORE - 3.0);
+
+        // trebuchet: whichever king moves first gives up its pawn, and the bishop shut in
+        // behind its own pawn can't wait instead; passing looks safe, so only the
+        // verification search keeps the null move from cutting here
+        let mut position = Position::from_fen("8/8/8/4pK2/3kP3/1p6/1P6/B7 w - - 0 1");
+        let beta = position.evaluate(PieceColor::White) as f64;
+        for null_move_pruning in [false, true] {
+            let mut engine = Engine::new(PieceColor::White);
+            engine.options.null_move_pruning = null_move_pruning;
+            let depth = NULL_MOVE_VERIFICATION_DEPTH;
+            let weakness = Weakness::for_skill(MAX_SKILL_LEVEL, depth);
+            let mut searcher = engine.searcher(&weakness, None);
+            let node = Node::new(beta - 1.0, beta, 0);
+            let value = searcher.negamax(&mut position, PieceColor::White, depth, node);
+            assert!(value < beta, "{} {}", null_move_pruning, value);
         }
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        // white to move mates with Qb8, any other queen move along the first rank is slower
+        let game = Game::new("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1", None);
+        let mut engine = Engine::new(PieceColor::White);
+        engine.options.multi_pv = 3;
+        let lines = engine.analyse(&game, PieceColor::White);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].moves[0].to_string(), "b1b8");
+        assert!(lines[0].score >= MATE_SCORE - MAX_PLY as f64);
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert!(lines.iter().all(|line| !line.moves.is_empty()));
+    }
+
+    #[test]
+    fn test_lazy_smp() {
+        // back rank mate, found the same way however many threads search
+        let game = Game::new("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", None);
+        let mut engine = Engine::new(PieceColor::White);
+        engine.options.threads = 3;
+        let (score, mv) = engine.search(&game, PieceColor::White);
+        let mv = mv.unwrap();
+        assert_eq!(mv.to_string(), "a1a8");
+        assert!(score >= MATE_SCORE - MAX_PLY as f64);
+    }
+
+    #[test]
+    fn test_complete_moves() {
+        // the pawn has to promote, and the move says so
+        let game = Game::new("8/4P1k1/8/8/8/8/8/K7 w - - 0 1", None);
+        let mut engine = Engine::new(PieceColor::White);
+        let mv = engine.generate_best_move(&game, PieceColor::White).unwrap();
+        assert_eq!((mv.from, mv.to, mv.promote.as_str()), (12, 4, "q"));
+        assert_eq!(mv.move_type, MoveType::Promotion);
+        assert!(game.clone().make_move(&mv).0);
+
+        // en passant takes the pawn beside it
+        let game = Game::new("4k3/8/8/3pP3/8/8/8/4K3 w - d5 0 2", None);
+        let ep = to_move(
+            &game,
+            &PossibleMoves {
+                from: 28,
+                to: 19,
+                piece: ChessPiece::WPawn,
+                promote: false,
+                promote_to: None,
+                move_type: MoveType::Normal,
+                captured_piece: ChessPiece::None,
+            },
+        );
+        assert_eq!(ep.move_type, MoveType::EnPassant);
+        assert_eq!(ep.captured_piece, ChessPiece::BPawn);
+        assert!(game.clone().make_move(&ep).0);
+
+        // stalemate is no move at all rather than a made up one
+        let game = Game::new("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", None);
+        let mut engine = Engine::new(PieceColor::Black);
+        assert!(engine
+            .generate_best_move(&game, PieceColor::Black)
+            .is_none());
+    }
 
-        if beta <= alpha {
-            break; // Beta cutoff
+    #[test]
+    fn test_ponder() {
+        let mut game = Game::new(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            None,
+        );
+        let mut engine = Engine::new(PieceColor::White);
+        engine.options.depth = 2;
+        let (_, mv) = engine.search(&game, PieceColor::White);
+        assert_eq!(engine.stats.pv.first(), mv.as_ref());
+        // the line holds the expected reply and plays out move by move
+        assert!(engine.stats.pv.len() >= 2);
+        let mut line = game.clone();
+        for mv in &engine.stats.pv {
+            let move_ = mv.to_move(&line);
+            assert!(line.make_move(&move_).0);
         }
+        assert!(game.make_move(&mv.unwrap().to_move(&game)).0);
+
+        // a miss stops the search and leaves the engine as it was
+        let ponder = engine.start_ponder(&game).unwrap();
+        engine.ponder_miss(ponder);
+
+        let ponder = engine.start_ponder(&game).unwrap();
+        let reply = ponder.expected.to_move(&game);
+        assert!(ponder.is_hit(&reply));
+        let underpromotion = Move {
+            move_type: MoveType::Promotion,
+            promote: String::from("n"),
+            ..ponder.expected.to_move(&game)
+        };
+        assert!(!ponder.is_hit(&underpromotion));
+        let position = ponder.position.clone();
+        let (_, mv) = engine.ponder_hit(ponder);
+        assert!(
+            position
+                .clone()
+                .make_move(&mv.unwrap().to_move(&position))
+                .0
+        );
+        // the hit keeps the full depth rather than whatever iteration had finished
+        assert_eq!(engine.stats.depth, engine.options.depth);
     }
-    (best_value, best_move)
 }
@@ -0,0 +1,476 @@
+use std::sync::Arc;
+
+use crate::interface::chessboard::piece::{board_to_fen, fen_to_board, ChessPiece, Color};
+
+use super::{
+    attacks::piece_color,
+    book::{castling_key, en_passant_key, piece_key, turn_key},
+    capture::{in_check, see},
+    evaluation::{imbalance, material_sum, mobility, tapered_king_safety, threats, MaterialCount},
+    heatmap::{
+        blend_heat, heat, piece_kind, BISHOP_PHASE, KNIGHT_PHASE, MAX_PHASE, PIECE_KINDS,
+        QUEEN_PHASE, ROOK_PHASE,
+    },
+    packed::PackedMove,
+    params::{eval_params, EvalParams},
+    pawns::pawn_structure,
+    play::MoveType,
+    square::{File, Square},
+};
+
+// castling rights, one bit each in the order of the fen letters KQkq
+pub const WHITE_KING_SIDE: u8 = 1;
+pub const WHITE_QUEEN_SIDE: u8 = 2;
+pub const BLACK_KING_SIDE: u8 = 4;
+pub const BLACK_QUEEN_SIDE: u8 = 8;
+
+// the castling field of a fen as bits, "-" or anything unknown is none
+pub fn castling_rights(field: &str) -> u8 {
+    field.chars().fold(0, |rights, letter| {
+        rights
+            | match letter {
+                'K' => WHITE_KING_SIDE,
+                'Q' => WHITE_QUEEN_SIDE,
+                'k' => BLACK_KING_SIDE,
+                'q' => BLACK_QUEEN_SIDE,
+                _ => 0,
+            }
+    })
+}
+
+fn castling_field(rights: u8) -> String {
+    let field = [
+        (WHITE_KING_SIDE, 'K'),
+        (WHITE_QUEEN_SIDE, 'Q'),
+        (BLACK_KING_SIDE, 'k'),
+        (BLACK_QUEEN_SIDE, 'q'),
+    ]
+    .iter()
+    .filter(|(right, _)| rights & right != 0)
+    .map(|(_, letter)| letter)
+    .collect::<String>();
+    if field.is_empty() {
+        String::from("-")
+    } else {
+        field
+    }
+}
+
+// rights gone once anything moves from or to the square, a king or rook leaving home or a
+// rook taken at home
+fn rights_lost(square: Square) -> u8 {
+    match square {
+        Square::E1 => WHITE_KING_SIDE | WHITE_QUEEN_SIDE,
+        Square::H1 => WHITE_KING_SIDE,
+        Square::A1 => WHITE_QUEEN_SIDE,
+        Square::E8 => BLACK_KING_SIDE | BLACK_QUEEN_SIDE,
+        Square::H8 => BLACK_KING_SIDE,
+        Square::A8 => BLACK_QUEEN_SIDE,
+        _ => 0,
+    }
+}
+
+// where the rook goes from and to when the king castles to `king_to`
+fn castle_rook(king_to: Square) -> (Square, Square) {
+    let rank = king_to.rank();
+    if king_to.file() == File::G {
+        (Square::at(File::H, rank), Square::at(File::F, rank))
+    } else {
+        (Square::at(File::A, rank), Square::at(File::D, rank))
+    }
+}
+
+fn side(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+// everything make_move keeps up to date besides the board, put back whole on unmake_move
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct State {
+    castling: u8,
+    // the pawn that just moved two squares, as the fen of this game keeps it
+    en_passant: Option<Square>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    key: u64,
+    material: [MaterialCount; 2],
+    // opening, middle game and endgame heat map sums of each side, by piece kind
+    piece_square: [[[i32; 3]; PIECE_KINDS]; 2],
+}
+
+// what make_move hands back to take the move back again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Undo {
+    state: State,
+    captured: ChessPiece,
+}
+
+// one position the search and perft play moves on in place, instead of cloning a Game and
+// building a fen for every node; the polyglot key, material and piece square sums follow
+// the moves so nothing has to be counted again
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub board: [ChessPiece; 64],
+    pub turn: Color,
+    state: State,
+    params: Arc<EvalParams>,
+}
+
+impl Position {
+    pub fn from_fen(fen: &str) -> Self {
+        let parts = fen.split(' ').collect::<Vec<&str>>();
+        let turn = if parts.get(1) == Some(&"b") {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let number = |index: usize, default: u32| {
+            parts
+                .get(index)
+                .and_then(|count| count.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+        let mut position = Position {
+            board: [ChessPiece::None; 64],
+            turn,
+            state: State {
+                castling: 0,
+                en_passant: None,
+                halfmove_clock: number(4, 0),
+                fullmove_number: number(5, 1),
+                key: turn_key(turn == Color::White),
+                material: [MaterialCount::default(); 2],
+                piece_square: [[[0; 3]; PIECE_KINDS]; 2],
+            },
+            params: eval_params(),
+        };
+        for (index, piece) in fen_to_board(fen).into_iter().enumerate() {
+            if let Some(square) = Square::new(index as i32) {
+                position.put(square, piece);
+            }
+        }
+        position.set_castling(castling_rights(parts.get(2).copied().unwrap_or("-")));
+        position.set_en_passant(
+            parts
+                .get(3)
+                .and_then(|square| Square::from_algebraic(square)),
+        );
+        position
+    }
+
+    pub fn fen(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            board_to_fen(&self.board),
+            if self.turn == Color::White { "w" } else { "b" },
+            castling_field(self.state.castling),
+            self.state
+                .en_passant
+                .map_or(String::from("-"), |square| square.to_string()),
+            self.state.halfmove_clock,
+            self.state.fullmove_number
+        )
+    }
+
+    // polyglot hash, the same polyglot_key gives for the fen
+    pub fn key(&self) -> u64 {
+        self.state.key
+    }
+
+    pub fn castling(&self) -> u8 {
+        self.state.castling
+    }
+
+    pub fn en_passant(&self) -> Option<Square> {
+        self.state.en_passant
+    }
+
+    pub fn material(&self, color: Color) -> MaterialCount {
+        self.state.material[side(color)]
+    }
+
+    // same as game_phase, from the material counts
+    pub fn phase(&self) -> i32 {
+        let phase: i32 = self
+            .state
+            .material
+            .iter()
+            .map(|count| {
+                count.knights * KNIGHT_PHASE
+                    + count.bishops * BISHOP_PHASE
+                    + count.rooks * ROOK_PHASE
+                    + count.queens * QUEEN_PHASE
+            })
+            .sum();
+        phase.min(MAX_PHASE)
+    }
+
+    // whether the side to move is in check
+    pub fn in_check(&self) -> bool {
+        in_check(&self.board, self.turn)
+    }
+
+    fn put(&mut self, square: Square, piece: ChessPiece) {
+        let color = match piece_color(piece) {
+            Some(color) => color,
+            None => return,
+        };
+        let index = square.index();
+        self.board[index as usize] = piece;
+        self.state.key ^= piece_key(piece, index);
+        self.count(piece, color, 1);
+        if let Some(kind) = piece_kind(piece) {
+            let values = heat(&self.params.piece_square, piece, color, index);
+            let sums = &mut self.state.piece_square[side(color)][kind];
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += value;
+            }
+        }
+    }
+
+    fn remove(&mut self, square: Square) -> ChessPiece {
+        let index = square.index();
+        let piece = self.board[index as usize];
+        let color = match piece_color(piece) {
+            Some(color) => color,
+            None => return piece,
+        };
+        self.board[index as usize] = ChessPiece::None;
+        self.state.key ^= piece_key(piece, index);
+        self.count(piece, color, -1);
+        if let Some(kind) = piece_kind(piece) {
+            let values = heat(&self.params.piece_square, piece, color, index);
+            let sums = &mut self.state.piece_square[side(color)][kind];
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum -= value;
+            }
+        }
+        piece
+    }
+
+    fn count(&mut self, piece: ChessPiece, color: Color, change: i32) {
+        let count = &mut self.state.material[side(color)];
+        match piece {
+            ChessPiece::WPawn | ChessPiece::BPawn => count.pawns += change,
+            ChessPiece::WKnight | ChessPiece::BKnight => count.knights += change,
+            ChessPiece::WBishop | ChessPiece::BBishop => count.bishops += change,
+            ChessPiece::WRook | ChessPiece::BRook => count.rooks += change,
+            ChessPiece::WQueen | ChessPiece::BQueen => count.queens += change,
+            _ => {}
+        }
+    }
+
+    fn set_castling(&mut self, rights: u8) {
+        self.state.key ^= castling_key(self.state.castling) ^ castling_key(rights);
+        self.state.castling = rights;
+    }
+
+    // the en passant part of the key depends on the board and the side to move, so it is
+    // set once both are final for the move
+    fn set_en_passant(&mut self, pawn: Option<Square>) {
+        let white_to_move = self.turn == Color::White;
+        self.state.key ^= en_passant_key(&self.board, self.state.en_passant, white_to_move);
+        self.state.en_passant = pawn;
+        self.state.key ^= en_passant_key(&self.board, pawn, white_to_move);
+    }
+
+    fn pass_turn(&mut self) {
+        self.state.key ^= turn_key(self.turn == Color::White);
+        self.turn = self.turn.toggle();
+        self.state.key ^= turn_key(self.turn == Color::White);
+    }
+
+    // plays a move from the move generator; None, with the position as it was, when the move
+    // would leave the own king in check
+    pub fn make_move(&mut self, mv: PackedMove) -> Option<Undo> {
+        let (from, to) = (mv.from(), mv.to());
+        let piece = self.board[from.index() as usize];
+        let color = self.turn;
+        if piece_color(piece) != Some(color) {
+            return None;
+        }
+        let captured = mv.captured_piece(&self.board);
+        let undo = Undo {
+            state: self.state,
+            captured,
+        };
+        // the old en passant square goes while the board is still the one it was hashed with
+        self.set_en_passant(None);
+
+        if mv.move_type() == MoveType::EnPassant {
+            self.remove(Square::at(to.file(), from.rank()));
+        } else {
+            self.remove(to);
+        }
+        self.remove(from);
+        match mv.promotion() {
+            Some(promotion) => self.put(to, promotion.piece(color)),
+            None => self.put(to, piece),
+        }
+        if mv.move_type() == MoveType::Castle {
+            let (rook_from, rook_to) = castle_rook(to);
+            let rook = self.remove(rook_from);
+            self.put(rook_to, rook);
+        }
+
+        self.set_castling(self.state.castling & !(rights_lost(from) | rights_lost(to)));
+        let is_pawn = piece == ChessPiece::WPawn || piece == ChessPiece::BPawn;
+        if is_pawn || captured != ChessPiece::None {
+            self.state.halfmove_clock = 0;
+        } else {
+            self.state.halfmove_clock += 1;
+        }
+        if color == Color::Black {
+            self.state.fullmove_number += 1;
+        }
+        self.pass_turn();
+        if is_pawn && from.rank_distance(to) == 2 {
+            self.set_en_passant(Some(to));
+        }
+
+        if in_check(&self.board, color) {
+            self.unmake_move(mv, undo);
+            return None;
+        }
+        Some(undo)
+    }
+
+    // takes back the move make_move played and gave `undo` for
+    pub fn unmake_move(&mut self, mv: PackedMove, undo: Undo) {
+        let (from, to) = (mv.from(), mv.to());
+        self.turn = self.turn.toggle();
+        let piece = match mv.promotion() {
+            Some(_) if self.turn == Color::White => ChessPiece::WPawn,
+            Some(_) => ChessPiece::BPawn,
+            None => self.board[to.index() as usize],
+        };
+        self.board[to.index() as usize] = ChessPiece::None;
+        self.board[from.index() as usize] = piece;
+        match mv.move_type() {
+            MoveType::EnPassant => {
+                let beside = Square::at(to.file(), from.rank());
+                self.board[beside.index() as usize] = undo.captured;
+            }
+            MoveType::Castle => {
+                let (rook_from, rook_to) = castle_rook(to);
+                self.board[rook_from.index() as usize] = self.board[rook_to.index() as usize];
+                self.board[rook_to.index() as usize] = ChessPiece::None;
+            }
+            _ => self.board[to.index() as usize] = undo.captured,
+        }
+        self.state = undo.state;
+    }
+
+    // pass the turn to the opponent without moving (used by null-move pruning)
+    pub fn make_null_move(&mut self) -> Undo {
+        let undo = Undo {
+            state: self.state,
+            captured: ChessPiece::None,
+        };
+        // en passant is only available right after the double push
+        self.set_en_passant(None);
+        self.pass_turn();
+        undo
+    }
+
+    pub fn unmake_null_move(&mut self, undo: Undo) {
+        self.turn = self.turn.toggle();
+        self.state = undo.state;
+    }
+
+    // same score as trace_board, the material and piece squares as make_move kept them
+    pub fn evaluate(&self, color: Color) -> i32 {
+        let params = &*self.params;
+        let phase = self.phase();
+        let side_total = |side_color: Color| {
+            let count = &self.state.material[side(side_color)];
+            let piece_square = self.state.piece_square[side(side_color)]
+                .iter()
+                .map(|sum| blend_heat(*sum, phase))
+                .sum::<i32>();
+            material_sum(&params.material, count)
+                + imbalance(&params.material, count)
+                + piece_square
+                + pawn_structure(params, &self.board, side_color, phase)
+                + tapered_king_safety(params, &self.board, side_color, phase)
+                + mobility(params, &self.board, side_color, phase)
+                + threats(params, &self.board, side_color)
+        };
+        let white_score = side_total(Color::White) - side_total(Color::Black);
+        if color == Color::White {
+            white_score
+        } else {
+            -white_score
+        }
+    }
+
+    // static exchange evaluation of `mv` with the material values the position was built with
+    pub fn see(&self, mv: PackedMove) -> i32 {
+        see(&self.board, mv, &self.params.material)
+    }
+
+    // positions with only king and pawns are where zugzwang makes null moves unsound
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        let count = self.material(color);
+        count.knights + count.bishops + count.rooks + count.queens > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{book::polyglot_key, evaluation::trace_board, movegen::MoveList};
+
+    // plays every move to `depth` and checks the position after each against one built from
+    // its fen, its score against trace_board, and that taking the move back restores it
+    fn walk(position: &mut Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = MoveList::new();
+        position.generate_moves(&mut moves);
+        let mut nodes = 0;
+        for mv in moves.iter().copied() {
+            let before = position.clone();
+            let undo = match position.make_move(mv) {
+                Some(undo) => undo,
+                None => {
+                    assert_eq!(*position, before, "{} {}", before.fen(), mv);
+                    continue;
+                }
+            };
+            let fresh = Position::from_fen(&position.fen());
+            assert_eq!(position.state, fresh.state, "{} {}", before.fen(), mv);
+            assert_eq!(position.key(), polyglot_key(&position.fen()));
+            let board = fen_to_board(&position.fen());
+            for color in [Color::White, Color::Black] {
+                let traced = trace_board(&eval_params(), &board, color).score;
+                assert_eq!(position.evaluate(color), traced, "{}", position.fen());
+            }
+            nodes += walk(position, depth - 1);
+            position.unmake_move(mv, undo);
+            assert_eq!(*position, before, "{} {}", before.fen(), mv);
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_make_unmake() {
+        // castling both ways, en passant and promotions are all a move or two away
+        let mut position = Position::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        );
+        assert_eq!(walk(&mut position, 2), 2039);
+
+        let mut position = Position::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1");
+        assert_eq!(walk(&mut position, 2), 496);
+
+        let undo = position.make_null_move();
+        assert_eq!(position.turn, Color::White);
+        position.unmake_null_move(undo);
+        assert_eq!(position.turn, Color::Black);
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt;
+
+use crate::interface::chessboard::piece::Color;
+
+// board arrays run from a8 (0) to h1 (63), black's back rank first, so the index of a
+// square is (7 - rank) * 8 + file
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum File {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+}
+
+impl File {
+    pub const ALL: [File; 8] = [
+        File::A,
+        File::B,
+        File::C,
+        File::D,
+        File::E,
+        File::F,
+        File::G,
+        File::H,
+    ];
+
+    // 0 is the a file
+    pub fn new(index: i32) -> Option<File> {
+        (0..8).contains(&index).then(|| File::ALL[index as usize])
+    }
+
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_char(letter: char) -> Option<File> {
+        File::new(letter as i32 - 'a' as i32)
+    }
+
+    pub fn to_char(self) -> char {
+        (b'a' + self as u8) as char
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rank {
+    First,
+    Second,
+    Third,
+    Fourth,
+    Fifth,
+    Sixth,
+    Seventh,
+    Eighth,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 8] = [
+        Rank::First,
+        Rank::Second,
+        Rank::Third,
+        Rank::Fourth,
+        Rank::Fifth,
+        Rank::Sixth,
+        Rank::Seventh,
+        Rank::Eighth,
+    ];
+
+    // 0 is the first rank, white's back rank
+    pub fn new(index: i32) -> Option<Rank> {
+        (0..8).contains(&index).then(|| Rank::ALL[index as usize])
+    }
+
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_char(digit: char) -> Option<Rank> {
+        Rank::new(digit as i32 - '1' as i32)
+    }
+
+    pub fn to_char(self) -> char {
+        (b'1' + self as u8) as char
+    }
+
+    // where the pieces of `color` start
+    pub fn back(color: Color) -> Rank {
+        if color == Color::White {
+            Rank::First
+        } else {
+            Rank::Eighth
+        }
+    }
+
+    // where the pawns of `color` start
+    pub fn pawn_start(color: Color) -> Rank {
+        if color == Color::White {
+            Rank::Second
+        } else {
+            Rank::Seventh
+        }
+    }
+
+    // where the pawns of `color` promote
+    pub fn promotion(color: Color) -> Rank {
+        if color == Color::White {
+            Rank::Eighth
+        } else {
+            Rank::First
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+// (files, ranks) steps for Square::offset, the way each piece moves
+pub const STRAIGHT: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+pub const DIAGONAL: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+// straight ones first, then the diagonals
+pub const ALL_WAYS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+pub const KNIGHT_JUMPS: [(i32, i32); 8] = [
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+];
+
+impl Square {
+    pub const A1: Square = Square::at(File::A, Rank::First);
+    pub const B1: Square = Square::at(File::B, Rank::First);
+    pub const C1: Square = Square::at(File::C, Rank::First);
+    pub const D1: Square = Square::at(File::D, Rank::First);
+    pub const E1: Square = Square::at(File::E, Rank::First);
+    pub const F1: Square = Square::at(File::F, Rank::First);
+    pub const G1: Square = Square::at(File::G, Rank::First);
+    pub const H1: Square = Square::at(File::H, Rank::First);
+    pub const A8: Square = Square::at(File::A, Rank::Eighth);
+    pub const B8: Square = Square::at(File::B, Rank::Eighth);
+    pub const C8: Square = Square::at(File::C, Rank::Eighth);
+    pub const D8: Square = Square::at(File::D, Rank::Eighth);
+    pub const E8: Square = Square::at(File::E, Rank::Eighth);
+    pub const F8: Square = Square::at(File::F, Rank::Eighth);
+    pub const G8: Square = Square::at(File::G, Rank::Eighth);
+    pub const H8: Square = Square::at(File::H, Rank::Eighth);
+
+    // None for anything that isn't a board index
+    pub fn new(index: i32) -> Option<Square> {
+        (0..64).contains(&index).then_some(Square(index as u8))
+    }
+
+    pub const fn at(file: File, rank: Rank) -> Square {
+        Square((7 - rank as u8) * 8 + file as u8)
+    }
+
+    pub fn index(self) -> i32 {
+        self.0 as i32
+    }
+
+    pub fn file(self) -> File {
+        File::ALL[(self.0 % 8) as usize]
+    }
+
+    pub fn rank(self) -> Rank {
+        Rank::ALL[(7 - self.0 / 8) as usize]
+    }
+
+    // the square `files` to the right and `ranks` up as white sees the board, None when
+    // that is off the board instead of wrapping round to the other side
+    pub fn offset(self, files: i32, ranks: i32) -> Option<Square> {
+        let file = File::new(self.file().index() + files)?;
+        let rank = Rank::new(self.rank().index() + ranks)?;
+        Some(Square::at(file, rank))
+    }
+
+    // the same square seen from black's side, a1 becomes a8
+    pub fn mirror(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+
+    // files between the two squares, whichever way
+    pub fn file_distance(self, other: Square) -> i32 {
+        (self.file().index() - other.file().index()).abs()
+    }
+
+    // ranks between the two squares, whichever way
+    pub fn rank_distance(self, other: Square) -> i32 {
+        (self.rank().index() - other.rank().index()).abs()
+    }
+
+    // squares such as e4, None for anything else
+    pub fn from_algebraic(text: &str) -> Option<Square> {
+        let mut chars = text.chars();
+        let file = File::from_char(chars.next()?)?;
+        let rank = Rank::from_char(chars.next()?)?;
+        chars.next().is_none().then_some(Square::at(file, rank))
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file().to_char(), self.rank().to_char())
+    }
+}
+
+impl From<Square> for i32 {
+    fn from(square: Square) -> i32 {
+        square.index()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_conversions() {
+        assert_eq!(Square::A8.index(), 0);
+        assert_eq!(Square::E1.index(), 60);
+        assert_eq!(Square::from_algebraic("e4").map(Square::index), Some(36));
+        assert_eq!(Square::new(36).unwrap().to_string(), "e4");
+        assert_eq!(Square::from_algebraic("i4"), None);
+        assert_eq!(Square::from_algebraic("e9"), None);
+        assert_eq!(Square::from_algebraic("e44"), None);
+        assert_eq!(Square::new(64), None);
+        assert_eq!(Square::new(-1), None);
+
+        // h-file squares don't wrap round to the a file
+        let h4 = Square::from_algebraic("h4").unwrap();
+        assert_eq!(h4.offset(1, 1), None);
+        assert_eq!(h4.offset(-1, 1).map(|s| s.to_string()), Some("g5".into()));
+        assert_eq!(Square::A1.offset(0, -1), None);
+
+        assert_eq!(Square::E1.mirror(), Square::E8);
+        assert_eq!(Square::B1.file(), File::B);
+        assert_eq!(Square::B1.rank(), Rank::back(Color::White));
+        assert_eq!(Square::G8.file_distance(Square::E8), 2);
+        assert_eq!(Rank::promotion(Color::Black), Rank::First);
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::packed::PackedMove;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// scores further from zero than this are mates or tablebase wins, which are stored
+// relative to the node so they stay right when found again at another ply
+const MATE_BOUND: f64 = 100_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // the score is at least this, the search failed high
+    Lower,
+    // the score is at most this, the search failed low
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TtEntry {
+    pub score: f64,
+    pub depth: i32,
+    pub bound: Bound,
+    pub best_move: Option<PackedMove>,
+}
+
+// one table shared by every search thread; each slot keeps the key xor-ed with the data
+// so a slot torn by two threads writing at once reads as a miss instead of a wrong entry
+#[derive(Debug)]
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+// two tables are only equal when they are the same table
+impl PartialEq for TranspositionTable {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+fn pack(entry: &TtEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    let mut data = (entry.score.round() as i32 as u32) as u64;
+    data |= (entry.depth.clamp(0, 255) as u64) << 32;
+    data |= bound << 40;
+    if let Some(best_move) = entry.best_move {
+        data |= (best_move.bits() as u64) << 42;
+    }
+    data
+}
+
+fn unpack(data: u64) -> TtEntry {
+    let bound = match (data >> 40) & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+    let best_move = Some(PackedMove::from_bits((data >> 42) as u16)).filter(|mv| !mv.is_none());
+    TtEntry {
+        score: data as u32 as i32 as f64,
+        depth: ((data >> 32) & 255) as i32,
+        bound,
+        best_move,
+    }
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes.max(1) << 20) / std::mem::size_of::<[AtomicU64; 2]>();
+        TranspositionTable {
+            slots: (0..count)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64, ply: i32) -> Option<TtEntry> {
+        let slot = self.slot(key);
+        let check = slot[0].load(Ordering::Relaxed);
+        let data = slot[1].load(Ordering::Relaxed);
+        if data == 0 || check ^ data != key {
+            return None;
+        }
+        let mut entry = unpack(data);
+        if entry.score > MATE_BOUND {
+            entry.score -= ply as f64;
+        } else if entry.score < -MATE_BOUND {
+            entry.score += ply as f64;
+        }
+        Some(entry)
+    }
+
+    // always replaces, the latest search knows the most about the position
+    pub fn store(&self, key: u64, ply: i32, mut entry: TtEntry) {
+        if entry.score > MATE_BOUND {
+            entry.score += ply as f64;
+        } else if entry.score < -MATE_BOUND {
+            entry.score -= ply as f64;
+        }
+        let data = pack(&entry);
+        let slot = self.slot(key);
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{play::MoveType, square::Square};
+
+    #[test]
+    fn test_store_and_probe() {
+        let table = TranspositionTable::new(1);
+        let entry = TtEntry {
+            score: -120.0,
+            depth: 4,
+            bound: Bound::Lower,
+            best_move: Square::from_algebraic("e2")
+                .zip(Square::from_algebraic("e4"))
+                .map(|(from, to)| PackedMove::new(from, to, MoveType::Normal, None)),
+        };
+        table.store(0x1234_5678_9abc_def0, 3, entry);
+        assert_eq!(table.probe(0x1234_5678_9abc_def0, 7), Some(entry));
+        assert_eq!(table.probe(0x1234_5678_9abc_def1, 7), None);
+
+        // a mate 5 plies below a node at ply 3 is 9 plies from the root when the node comes
+        // up again at ply 4
+        let mate = TtEntry {
+            score: 1_000_000_000.0 - 8.0,
+            depth: 2,
+            bound: Bound::Exact,
+            best_move: None,
+        };
+        table.store(42, 3, mate);
+        assert_eq!(table.probe(42, 4).unwrap().score, 1_000_000_000.0 - 9.0);
+
+        table.clear();
+        assert_eq!(table.probe(42, 4), None);
+    }
+}
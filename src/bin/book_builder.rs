@@ -0,0 +1,183 @@
+// builds a polyglot opening book from pgn files
+//
+// usage: book_builder <book.bin> <games.pgn>... [--min-games <n>] [--min-elo <n>]
+//        [--max-ply <n>] [--win <n>] [--draw <n>] [--loss <n>]
+//
+// every move played by a rated enough player within the first plies is counted, weighted
+// by how the game ended for the side that played it, and moves seen in fewer than
+// --min-games games are left out
+
+use std::{collections::HashMap, env, fs, process};
+
+use chess_mater::actions::{
+    book::{encode_move, polyglot_key, BookEntry, OpeningBook},
+    pgn::{parse_pgn, san_to_move, PgnGame, START_FEN},
+    play::Game,
+};
+
+struct Options {
+    out: String,
+    pgn_files: Vec<String>,
+    min_games: u32,
+    min_elo: u32,
+    max_ply: usize,
+    // weight a move earns for a win, draw or loss of the side that played it
+    win: u64,
+    draw: u64,
+    loss: u64,
+}
+
+#[derive(Default)]
+struct MoveStats {
+    games: u32,
+    weight: u64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        out: String::new(),
+        pgn_files: vec![],
+        min_games: 3,
+        min_elo: 0,
+        max_ply: 20,
+        win: 2,
+        draw: 1,
+        loss: 0,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || -> Result<u64, String> {
+            let value = args.next().ok_or(format!("{} needs a value", arg))?;
+            value.parse().map_err(|_| format!("bad value for {}", arg))
+        };
+        match arg.as_str() {
+            "--min-games" => options.min_games = value()? as u32,
+            "--min-elo" => options.min_elo = value()? as u32,
+            "--max-ply" => options.max_ply = value()? as usize,
+            "--win" => options.win = value()?,
+            "--draw" => options.draw = value()?,
+            "--loss" => options.loss = value()?,
+            _ if options.out.is_empty() => options.out = arg,
+            _ => options.pgn_files.push(arg),
+        }
+    }
+    if options.out.is_empty() || options.pgn_files.is_empty() {
+        return Err(String::from(
+            "usage: book_builder <book.bin> <games.pgn>... [--min-games <n>] [--min-elo <n>] \
+             [--max-ply <n>] [--win <n>] [--draw <n>] [--loss <n>]",
+        ));
+    }
+    Ok(options)
+}
+
+fn elo(game: &PgnGame, tag: &str) -> u32 {
+    game.header(tag)
+        .and_then(|elo| elo.parse().ok())
+        .unwrap_or(0)
+}
+
+// counts the moves of one game, false when a move could not be read
+fn add_game(
+    options: &Options,
+    pgn_game: &PgnGame,
+    stats: &mut HashMap<(u64, u16), MoveStats>,
+) -> bool {
+    let white_score = match pgn_game.white_score() {
+        Some(score) => score,
+        None => return true,
+    };
+    let fen = pgn_game.header("FEN").unwrap_or(START_FEN);
+    let mut game = Game::new(fen, None);
+
+    for (ply, san) in pgn_game.moves.iter().take(options.max_ply).enumerate() {
+        let move_ = match san_to_move(&game, san) {
+            Some(move_) => move_,
+            None => return false,
+        };
+        let white_to_move = game.fen.split(" ").nth(1) != Some("b");
+        let (player_elo, score) = if white_to_move {
+            (elo(pgn_game, "WhiteElo"), white_score)
+        } else {
+            (elo(pgn_game, "BlackElo"), 1.0 - white_score)
+        };
+
+        if player_elo >= options.min_elo {
+            let weight = if score == 1.0 {
+                options.win
+            } else if score == 0.0 {
+                options.loss
+            } else {
+                options.draw
+            };
+            let entry = stats
+                .entry((polyglot_key(&game.fen), encode_move(&move_)))
+                .or_default();
+            entry.games += 1;
+            entry.weight += weight;
+        }
+
+        if !game.make_move(&move_).0 {
+            println!("stopped at ply {}, {} was not accepted", ply, san);
+            return false;
+        }
+    }
+    true
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut stats = HashMap::new();
+    let mut games = 0;
+    let mut unreadable = 0;
+    for path in &options.pgn_files {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        for pgn_game in parse_pgn(&text) {
+            games += 1;
+            if !add_game(&options, &pgn_game, &mut stats) {
+                unreadable += 1;
+            }
+        }
+    }
+
+    let kept = stats
+        .into_iter()
+        .filter(|(_, stats)| stats.games >= options.min_games && stats.weight > 0)
+        .collect::<Vec<_>>();
+    // polyglot weights are 16 bits, scale down when a popular move goes past that
+    let max_weight = kept
+        .iter()
+        .map(|(_, stats)| stats.weight)
+        .max()
+        .unwrap_or(0);
+    let scale = if max_weight > u16::MAX as u64 {
+        u16::MAX as f64 / max_weight as f64
+    } else {
+        1.0
+    };
+    let entries = kept
+        .into_iter()
+        .map(|((key, move_), stats)| BookEntry {
+            key,
+            move_,
+            weight: ((stats.weight as f64 * scale) as u16).max(1),
+            learn: 0,
+        })
+        .collect::<Vec<_>>();
+
+    let book = OpeningBook::from_entries(entries);
+    book.save(&options.out)?;
+    println!(
+        "{} games, {} with unreadable moves, {} book entries written to {}",
+        games,
+        unreadable,
+        book.len(),
+        options.out
+    );
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = parse_options().and_then(run) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,53 @@
+// generates distance to mate tables for endgames of up to four pieces
+//
+// usage: endgame_generator <directory> <table>... such as KQvK KRvK KPvK KQvKR
+//
+// the smaller tables a capture or promotion leads to are generated first and written
+// as well, tables already in the directory are loaded instead of generated again
+
+use std::{env, path::Path, process, time::Instant};
+
+use chess_mater::actions::endgame::{EndgameTables, ENDGAME_TABLE_EXTENSION};
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    if args.len() < 2 {
+        return Err(String::from(
+            "usage: endgame_generator <directory> <table>... such as KQvK KRvK KPvK",
+        ));
+    }
+    let directory = &args[0];
+    let mut tables = if Path::new(directory).is_dir() {
+        EndgameTables::load_dir(directory)?
+    } else {
+        std::fs::create_dir_all(directory).map_err(|err| format!("{}: {}", directory, err))?;
+        EndgameTables::default()
+    };
+    let existing = tables.names().into_iter().cloned().collect::<Vec<String>>();
+
+    for name in &args[1..] {
+        let start = Instant::now();
+        tables.generate(name)?;
+        println!("{} done in {:.1}s", name, start.elapsed().as_secs_f64());
+    }
+
+    let mut names = tables.names().into_iter().cloned().collect::<Vec<String>>();
+    names.sort();
+    for name in names.iter().filter(|name| !existing.contains(name)) {
+        let table = tables.get(name).ok_or(format!("{} went missing", name))?;
+        let path = Path::new(directory).join(format!("{}.{}", name, ENDGAME_TABLE_EXTENSION));
+        table.save(&path.to_string_lossy())?;
+        println!(
+            "{} written, longest mate {} moves",
+            path.display(),
+            table.longest_mate()
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run(env::args().skip(1).collect()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,261 @@
+// texel tuning of the evaluation parameters
+//
+// usage: tuner <positions> [--params <json>] [--out <json>] [--passes <n>] [--step <n>]
+//
+// every line of the positions file holds a quiet fen and the result of the game it was
+// taken from, either as 1-0 / 1/2-1/2 / 0-1 or as 1.0 / 0.5 / 0.0 from white's side,
+// for example `rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1; 1/2-1/2`
+
+use std::{env, fs, process, thread};
+
+use chess_mater::{
+    actions::{evaluation::trace_board, params::EvalParams},
+    interface::chessboard::piece::{fen_to_board, ChessPiece, Color},
+};
+use serde_json::Value;
+
+struct Position {
+    board: [ChessPiece; 64],
+    result: f64,
+}
+
+struct Options {
+    positions: String,
+    params: Option<String>,
+    out: String,
+    passes: usize,
+    step: i64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        positions: String::new(),
+        params: None,
+        out: String::from("eval_params.json"),
+        passes: 100,
+        step: 1,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--params" => options.params = Some(value()?),
+            "--out" => options.out = value()?,
+            "--passes" => options.passes = value()?.parse().map_err(|_| "bad --passes")?,
+            "--step" => options.step = value()?.parse().map_err(|_| "bad --step")?,
+            _ if options.positions.is_empty() => options.positions = arg,
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    if options.positions.is_empty() {
+        return Err(String::from(
+            "usage: tuner <positions> [--params <json>] [--out <json>] [--passes <n>] [--step <n>]",
+        ));
+    }
+    Ok(options)
+}
+
+// game result from white's side, the label is whatever follows the fen
+fn parse_result(label: &str) -> Option<f64> {
+    if label.contains("1/2-1/2") {
+        return Some(0.5);
+    }
+    if label.contains("1-0") {
+        return Some(1.0);
+    }
+    if label.contains("0-1") {
+        return Some(0.0);
+    }
+    let number = label.trim_matches(|c: char| !(c.is_ascii_digit() || c == '.'));
+    match number.parse::<f64>() {
+        Ok(result) if (0.0..=1.0).contains(&result) => Some(result),
+        _ => None,
+    }
+}
+
+fn load_positions(path: &str) -> Result<Vec<Position>, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut positions = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // the fen is the first six fields, everything after it is the label
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 7 {
+            return Err(format!("{}:{}: expected a fen and a result", path, number + 1));
+        }
+        let fen = fields[..6].join(" ").trim_end_matches(';').to_string();
+        let result = parse_result(&fields[6..].join(" "))
+            .ok_or(format!("{}:{}: unknown result", path, number + 1))?;
+        positions.push(Position {
+            board: fen_to_board(&fen),
+            result,
+        });
+    }
+    Ok(positions)
+}
+
+// expected score for white from a centipawn evaluation
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// mean squared difference between the game results and the predicted results
+fn error(positions: &[Position], params: &EvalParams, k: f64) -> f64 {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = positions.len().div_ceil(threads).max(1);
+    let total: f64 = thread::scope(|scope| {
+        let workers = positions
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|position| {
+                            let score = trace_board(params, &position.board, Color::White).score;
+                            (position.result - sigmoid(score as f64, k)).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap_or(0.0))
+            .sum()
+    });
+    total / positions.len() as f64
+}
+
+// scaling constant that fits the starting parameters best, found by narrowing the step
+fn fit_k(positions: &[Position], params: &EvalParams) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = error(positions, params, best_k);
+    let mut step = 0.5;
+    while step > 0.001 {
+        let mut improved = false;
+        for k in [best_k - step, best_k + step] {
+            if k <= 0.0 {
+                continue;
+            }
+            let e = error(positions, params, k);
+            if e < best_error {
+                best_error = e;
+                best_k = k;
+                improved = true;
+            }
+        }
+        if !improved {
+            step /= 2.0;
+        }
+    }
+    best_k
+}
+
+// counts and percentages are structural, not weights, and stay as they are
+fn tunable(path: &str) -> bool {
+    !(path.ends_with("_base") || path.ends_with("threat_divisor") || path.ends_with("_percent"))
+}
+
+fn collect_weights(value: &Value, path: &str, weights: &mut Vec<(String, i64)>) {
+    match value {
+        Value::Number(number) => {
+            weights.push((path.to_string(), number.as_i64().unwrap_or_default()));
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_weights(item, &format!("{}[{}]", path, i), weights);
+            }
+        }
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                collect_weights(field, &format!("{}.{}", path, name), weights);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn apply_weights(value: &mut Value, weights: &[(String, i64)], index: &mut usize) {
+    match value {
+        Value::Number(_) => {
+            *value = Value::from(weights[*index].1);
+            *index += 1;
+        }
+        Value::Array(items) => {
+            for item in items {
+                apply_weights(item, weights, index);
+            }
+        }
+        Value::Object(fields) => {
+            for (_, field) in fields.iter_mut() {
+                apply_weights(field, weights, index);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn to_params(layout: &Value, weights: &[(String, i64)]) -> EvalParams {
+    let mut value = layout.clone();
+    apply_weights(&mut value, weights, &mut 0);
+    serde_json::from_value(value).unwrap_or_default()
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let positions = load_positions(&options.positions)?;
+    if positions.is_empty() {
+        return Err(format!("{}: no positions", options.positions));
+    }
+    let params = match &options.params {
+        Some(path) => EvalParams::load(path)?,
+        None => EvalParams::default(),
+    };
+    println!("{} positions", positions.len());
+
+    let layout = serde_json::to_value(&params).map_err(|err| err.to_string())?;
+    let mut weights = vec![];
+    collect_weights(&layout, "", &mut weights);
+
+    let k = fit_k(&positions, &params);
+    let mut best_error = error(&positions, &params, k);
+    println!("k {:.3}, starting error {:.6}", k, best_error);
+
+    // local search, nudge every weight both ways and keep whatever lowers the error
+    for pass in 1..=options.passes {
+        let mut improved = false;
+        for i in 0..weights.len() {
+            if !tunable(&weights[i].0) {
+                continue;
+            }
+            let start = weights[i].1;
+            for delta in [options.step, -options.step] {
+                weights[i].1 = start + delta;
+                let e = error(&positions, &to_params(&layout, &weights), k);
+                if e < best_error {
+                    best_error = e;
+                    improved = true;
+                    break;
+                }
+                weights[i].1 = start;
+            }
+        }
+        // written after every pass so a long run can be stopped at any time
+        to_params(&layout, &weights).save(&options.out)?;
+        println!("pass {}, error {:.6}", pass, best_error);
+        if !improved {
+            break;
+        }
+    }
+    println!("tuned parameters written to {}", options.out);
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = parse_options().and_then(run) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
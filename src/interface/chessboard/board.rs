@@ -6,6 +6,7 @@ use godot::engine::{GridContainer, GridContainerVirtual};
 use godot::prelude::*;
 
 use crate::actions::play::{GameState, Move, MoveType};
+use crate::actions::square::{self, Rank};
 use crate::interface::chessboard::piece::{piece_to_fen, string_to_piece};
 use crate::interface::chessboard::piece::{ChessPiece, Color as PieceColor, Piece};
 
@@ -195,9 +196,9 @@ impl Square {
         let piece = piece.unwrap().try_cast::<Piece>().unwrap();
         let piece = piece.bind();
 
-        if (piece.piece == ChessPiece::BPawn && to >= 56 && to <= 63)
-            || (piece.piece == ChessPiece::WPawn && to >= 0 && to <= 7)
-        {
+        let is_pawn = piece.piece == ChessPiece::BPawn || piece.piece == ChessPiece::WPawn;
+        let to_rank = square::Square::new(to).map(square::Square::rank);
+        if is_pawn && to_rank == Some(Rank::promotion(piece.piece.color())) {
             let parent = board_node.get_parent().unwrap();
             let pp = parent.get_parent().unwrap();
             let pp = pp.get_parent().unwrap();
@@ -417,20 +418,22 @@ impl Board {
             && piece_move.piece == ChessPiece::BKing
             && move_diff.abs() == 2
         {
-            Square::move_drag_element(
-                &board_node,
-                if move_diff > 0 { 0 } else { 7 },
-                if move_diff > 0 { 3 } else { 5 },
-            );
+            let (rook_from, rook_to) = if move_diff > 0 {
+                (square::Square::A8, square::Square::D8)
+            } else {
+                (square::Square::H8, square::Square::F8)
+            };
+            Square::move_drag_element(&board_node, rook_from.index(), rook_to.index());
         } else if piece_move.move_type == MoveType::Castle
             && piece_move.piece == ChessPiece::WKing
             && move_diff.abs() == 2
         {
-            Square::move_drag_element(
-                &board_node,
-                if move_diff > 0 { 56 } else { 63 },
-                if move_diff > 0 { 59 } else { 61 },
-            );
+            let (rook_from, rook_to) = if move_diff > 0 {
+                (square::Square::A1, square::Square::D1)
+            } else {
+                (square::Square::H1, square::Square::F1)
+            };
+            Square::move_drag_element(&board_node, rook_from.index(), rook_to.index());
         }
 
         if piece_move.promote.to_string().as_str() != "" {
@@ -1,7 +1,15 @@
+use std::thread::{self, JoinHandle};
+
 use crate::{
     actions::{
+        attacks::{attacked_squares, checkers, discovered_check_candidates, pins},
+        capture::hanging_pieces,
+        endgame::{Dtm, EndgameTables},
+        params::{eval_params, load_eval_params, set_eval_params, EvalParams, EVAL_PARAMS_FILE},
         path::enpassant_moves,
         play::{Game, Move, MoveType},
+        player::{trace_position, Engine, Ponder, MAX_SKILL_LEVEL},
+        square::Square,
     },
     interface::chessboard::piece::{ChessPiece, Color as PieceColor},
 };
@@ -29,6 +37,12 @@ pub struct MainGame {
     game: Game,
     game_over: bool,
     engine_color: PieceColor,
+    endgame_tables: Option<EndgameTables>,
+    // search on the human's time for the reply the engine expects, see settle_ponder
+    ponder: Option<Ponder>,
+    // the engine's turn searched off the main thread so the board keeps drawing, picked up
+    // in process once it is done
+    thinking: Option<JoinHandle<(Engine, Option<Move>)>>,
 }
 
 #[derive(Debug, GodotClass)]
@@ -53,9 +67,12 @@ impl Node2DVirtual for MainGame {
         MainGame {
             base,
             promotion_overlay: PackedScene::new(),
-            game: Game::new(&fen, None),
+            game: Game::new(&fen, Some(Engine::new(PieceColor::Black))),
             game_over: false,
             engine_color: PieceColor::Black,
+            endgame_tables: None,
+            ponder: None,
+            thinking: None,
             move_sound: None,
             capture_sound: None,
             castle_sound: None,
@@ -64,6 +81,11 @@ impl Node2DVirtual for MainGame {
     }
 
     fn ready(&mut self) {
+        if std::path::Path::new(EVAL_PARAMS_FILE).exists() {
+            if let Err(err) = load_eval_params(EVAL_PARAMS_FILE) {
+                godot_warn!("keeping default evaluation: {}", err);
+            }
+        }
         self.promotion_overlay = load("res://promote/modal_overlay.tscn");
         self.move_sound = Some(self.base.get_node_as::<AudioStreamPlayer>("MoveSound"));
         self.capture_sound = Some(self.base.get_node_as::<AudioStreamPlayer>("CaptureSound"));
@@ -101,9 +123,18 @@ impl Node2DVirtual for MainGame {
             self.engine_color.toggle(),
         );
         board_mut.orientation = self.engine_color.toggle();
-        // if self.engine_color == PieceColor::White {
-        //     // self.engine_play();
-        // }
+        if self.engine_color == PieceColor::White {
+            self.engine_play();
+        }
+    }
+
+    fn process(&mut self, _delta: f64) {
+        if !self.thinking.as_ref().is_some_and(JoinHandle::is_finished) {
+            return;
+        }
+        if let Some(Ok((engine, best_move))) = self.thinking.take().map(JoinHandle::join) {
+            self.play_engine_move(engine, best_move);
+        }
     }
 }
 
@@ -112,15 +143,43 @@ impl MainGame {
     #[signal]
     fn update_board();
 
+    // the engine's turn, searched on a thread of its own; a ponder hit only has to let the
+    // search that started on the human's time finish
     fn engine_play(&mut self) {
-        println!("engine play");
+        let mut engine = match self.game.engine.clone() {
+            Some(engine) => engine,
+            None => return,
+        };
+        let game = self.game.clone();
+        let color = self.engine_color;
+        let ponder = self.ponder.take();
+        self.thinking = Some(thread::spawn(move || {
+            let best_move = match ponder {
+                Some(ponder) => engine.ponder_hit(ponder).1.map(|mv| mv.to_move(&game)),
+                None => engine.generate_best_move(&game, color),
+            };
+            (engine, best_move)
+        }));
+    }
 
-        let engine = self.game.engine.clone();
-        if engine.is_none() {
+    fn play_engine_move(&mut self, mut engine: Engine, best_move: Option<Move>) {
+        let best_move = match best_move {
+            Some(best_move) => best_move,
+            // checkmated or stalemated, there is nothing to play
+            None => {
+                self.game_over = true;
+                return;
+            }
+        };
+        let (moved, check) = self.game.make_move(&best_move);
+        if !moved {
             return;
         }
-        let mut engine = engine.unwrap();
-        let best_move = engine.generate_best_move(&mut self.game, self.engine_color);
+        self.play_sound(&best_move, check);
+        self.game.turn = self.game.turn.toggle();
+        // think about the reply the search expects while the human thinks about theirs
+        self.ponder = engine.start_ponder(&self.game);
+        self.game.engine = Some(engine);
 
         let mut node = self.base.clone().cast::<MainGame>();
         node.emit_signal(
@@ -129,6 +188,21 @@ impl MainGame {
         );
     }
 
+    // the human moved: a ponder search on another reply is stopped and thrown away, one on
+    // the reply they played is left running for engine_play to finish
+    fn settle_ponder(&mut self, move_: &Move) {
+        if self
+            .ponder
+            .as_ref()
+            .is_some_and(|ponder| ponder.is_hit(move_))
+        {
+            return;
+        }
+        if let (Some(ponder), Some(engine)) = (self.ponder.take(), self.game.engine.as_mut()) {
+            engine.ponder_miss(ponder);
+        }
+    }
+
     fn play_sound(&mut self, move_: &Move, check: bool) {
         if move_.captured_piece == ChessPiece::None {
             if move_.move_type == MoveType::Castle {
@@ -179,7 +253,11 @@ impl MainGame {
     #[func]
     fn on_choose_piece(&mut self, piece: GodotString, from: i32, to: i32) {
         //pro,ote pawns
-        if self.game_over {
+        if self.game_over
+            || self.thinking.is_some()
+            || Square::new(from).is_none()
+            || Square::new(to).is_none()
+        {
             return;
         }
         let board_placement = fen_to_board(&self.game.fen);
@@ -200,20 +278,23 @@ impl MainGame {
         if moved {
             self.play_sound(&move_, check);
             self.game.turn = self.game.turn.toggle();
+            self.settle_ponder(&move_);
             let p_move = PieceMove::from_move(&move_);
             let mut node = self.base.clone().cast::<MainGame>();
             node.emit_signal("update_board".into(), &[Variant::from(p_move)]);
             if self.game.turn == self.engine_color && !self.game_over {
-                // sleep(Duration::from_millis(10000));
-                println!("engine play");
-                // self.engine_play(); //TODO: engine play is not working (threading issue)
+                self.engine_play();
             }
         }
     }
 
     #[func]
     fn on_trigger_move(&mut self, from: i32, to: i32) {
-        if self.game_over {
+        let (from_square, to_square) = match (Square::new(from), Square::new(to)) {
+            (Some(from_square), Some(to_square)) => (from_square, to_square),
+            _ => return,
+        };
+        if self.game_over || self.thinking.is_some() {
             return;
         }
         let board_placement = fen_to_board(&self.game.fen);
@@ -227,8 +308,8 @@ impl MainGame {
 
         let is_enp = enp_squares.contains(&to);
 
-        let is_casle =
-            (piece == ChessPiece::BKing || piece == ChessPiece::WKing) && (from - to).abs() == 2;
+        let is_casle = (piece == ChessPiece::BKing || piece == ChessPiece::WKing)
+            && from_square.file_distance(to_square) == 2;
 
         let move_tye = if is_enp {
             MoveType::EnPassant
@@ -254,13 +335,160 @@ impl MainGame {
             self.play_sound(&move_, check);
 
             self.game.turn = self.game.turn.toggle();
+            self.settle_ponder(&move_);
             let p_move = PieceMove::from_move(&move_);
             let mut node = self.base.clone().cast::<MainGame>();
             node.emit_signal("update_board".into(), &[Variant::from(p_move)]);
             if self.game.turn == self.engine_color && !self.game_over {
-                println!("engine play");
-                // self.engine_play();  //TODO: engine play is not working (threading issue)
+                self.engine_play();
+            }
+        }
+    }
+
+    // term by term breakdown of the current position's score, as json, from the side to move
+    #[func]
+    fn evaluation_trace(&self) -> GodotString {
+        let trace = trace_position(&self.game, self.game.turn);
+        serde_json::to_string(&trace).unwrap_or_default().into()
+    }
+
+    #[func]
+    fn load_eval_params(&mut self, path: GodotString) -> bool {
+        load_eval_params(&String::from(&path)).is_ok()
+    }
+
+    // replace the evaluation weights with a json document, as written by get_eval_params
+    #[func]
+    fn set_eval_params(&mut self, json: GodotString) -> bool {
+        match EvalParams::from_json(&String::from(&json)) {
+            Ok(params) => {
+                set_eval_params(params);
+                true
             }
+            Err(_) => false,
         }
     }
+
+    #[func]
+    fn get_eval_params(&self) -> GodotString {
+        eval_params().to_json().into()
+    }
+
+    // best `lines` moves of the side to move with their scores and expected continuations,
+    // as json, for hints and reviewing a game
+    #[func]
+    fn analyse(&mut self, lines: i32) -> GodotString {
+        let mut engine = self
+            .game
+            .engine
+            .clone()
+            .unwrap_or_else(|| Engine::new(self.game.turn));
+        engine.options.multi_pv = lines.max(1) as usize;
+        let lines = engine
+            .analyse(&self.game, self.game.turn)
+            .iter()
+            .map(|line| {
+                let moves = line
+                    .moves
+                    .iter()
+                    .map(|mv| {
+                        serde_json::json!({
+                            "from": mv.from().index(),
+                            "to": mv.to().index(),
+                            "move": mv.to_string(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                serde_json::json!({ "score": line.score, "moves": moves })
+            })
+            .collect::<Vec<_>>();
+        serde_json::Value::from(lines).to_string().into()
+    }
+
+    // squares of the side to move's pieces the opponent wins material by taking, as json
+    #[func]
+    fn hanging_pieces(&self) -> GodotString {
+        let board = fen_to_board(&self.game.fen);
+        serde_json::to_string(&hanging_pieces(&board, self.game.turn))
+            .unwrap_or_default()
+            .into()
+    }
+
+    // what the side to move's pieces are up against, as json: squares the opponent attacks,
+    // pieces giving check, pinned pieces with their rays and pieces that can uncover check
+    #[func]
+    fn attack_info(&self) -> GodotString {
+        let board = fen_to_board(&self.game.fen);
+        let color = self.game.turn;
+        let opponent = if color == PieceColor::White {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        serde_json::json!({
+            "attacked": attacked_squares(&board, opponent),
+            "checkers": checkers(&board, color),
+            "pins": pins(&board, color),
+            "discovered": discovered_check_candidates(&board, color),
+        })
+        .to_string()
+        .into()
+    }
+
+    // graded opponents, 0 to 20, or a rating that picks the level; elo 0 clears it
+    #[func]
+    fn set_skill_level(&mut self, level: i32, elo: i32) {
+        if let Some(engine) = self.game.engine.as_mut() {
+            engine.options.skill_level = level.clamp(0, MAX_SKILL_LEVEL as i32) as u8;
+            engine.options.target_elo = (elo > 0).then_some(elo as u32);
+        }
+    }
+
+    #[func]
+    fn set_threads(&mut self, threads: i32) {
+        if let Some(engine) = self.game.engine.as_mut() {
+            engine.options.threads = threads.max(1) as usize;
+        }
+    }
+
+    // generated endgame tables for the training mode, the engine gets a copy as well
+    #[func]
+    fn load_endgame_tables(&mut self, directory: GodotString) -> bool {
+        match EndgameTables::load_dir(&String::from(&directory)) {
+            Ok(tables) => {
+                if let Some(engine) = self.game.engine.as_mut() {
+                    engine.endgame_tables = Some(tables.clone());
+                }
+                self.endgame_tables = Some(tables);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    // perfect move and result for the side to move, as json, empty outside the tables
+    #[func]
+    fn endgame_hint(&self) -> GodotString {
+        let hint = self
+            .endgame_tables
+            .as_ref()
+            .and_then(|tables| tables.best_move(&self.game));
+        let (mv, dtm) = match hint {
+            Some(hint) => hint,
+            None => return GodotString::new(),
+        };
+        let (result, moves) = match dtm {
+            Dtm::Win(moves) => ("win", moves),
+            Dtm::Draw => ("draw", 0),
+            Dtm::Loss(moves) => ("loss", moves),
+        };
+        serde_json::json!({
+            "from": mv.from,
+            "to": mv.to,
+            "result": result,
+            "moves": moves,
+        })
+        .to_string()
+        .into()
+    }
 }
@@ -38,7 +38,7 @@ pub enum ChessPiece {
 }
 
 pub fn fen_to_board(fen: &str) -> [ChessPiece; 64] {
-    let board_fen = fen.split(' ').collect::<Vec<&str>>()[0];
+    let board_fen = fen.split(' ').next().unwrap_or_default();
     let mut board: [ChessPiece; 64] = [ChessPiece::None; 64];
     let mut sqr: usize = 0;
 
@@ -1,5 +1,5 @@
-mod interface;
-mod actions;
+pub mod interface;
+pub mod actions;
 
 use godot::prelude::*;
 